@@ -1,9 +1,17 @@
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 
 use clap::Parser;
-use tokio::sync::Mutex;
+use tokio::sync::{watch, Mutex, Notify};
+use tokio::task::JoinSet;
 
+use provider::SharedProvider;
+
+mod output;
 mod provider;
+mod refresh;
+mod state;
 mod target;
 mod web;
 
@@ -11,6 +19,35 @@ mod web;
 struct Cli {
     #[clap(long, default_value = "0.0.0.0:3716", env = "EXPOSER_ADDR")]
     addr: String,
+
+    /// Traefik dynamic configuration files (or directories of them) to merge
+    /// into the generated configuration.
+    #[clap(long = "file", env = "EXPOSER_FILES", value_delimiter = ',')]
+    files: Vec<PathBuf>,
+
+    /// Milliseconds without changes to wait for before rebuilding the
+    /// configuration, so bursts of events cause a single rebuild.
+    #[clap(long, default_value = "500", env = "EXPOSER_DEBOUNCE_MS")]
+    debounce_ms: u64,
+
+    /// Directory watched by Traefik's file provider to also write the
+    /// generated configuration to, so changes propagate without waiting for
+    /// Traefik's next poll.
+    #[clap(long, env = "EXPOSER_OUTPUT_DIR")]
+    output_dir: Option<PathBuf>,
+
+    /// Format of the configuration written to `--output-dir`.
+    #[clap(long, value_enum, default_value_t, env = "EXPOSER_OUTPUT_FORMAT")]
+    output_format: output::OutputFormat,
+
+    /// File the last generated configuration is persisted to. It is served,
+    /// flagged as stale, after a restart until the providers are synced, e.g.
+    /// while Docker is still down.
+    #[clap(long, env = "EXPOSER_STATE_FILE")]
+    state_file: Option<PathBuf>,
+
+    #[clap(flatten)]
+    docker: provider::docker::DockerOptions,
 }
 
 #[tokio::main]
@@ -19,17 +56,64 @@ async fn main() -> anyhow::Result<()> {
 
     let cli = Cli::parse();
 
-    let docker_client = Arc::new(Mutex::new(provider::docker::DockerProvider::new()?));
-    let mut receiver = {
-        docker_client.lock().await.watch_container_events(vec!["create".to_string(), "update".to_string(), "delete".to_string()])
+    let initial = match cli.state_file {
+        Some(ref state_file) => match state::load(state_file) {
+            Ok(Some(snapshot)) => {
+                println!("Serving the configuration persisted in {} until synced", state_file.display());
+                snapshot
+            }
+            Ok(None) => refresh::Snapshot::default(),
+            Err(e) => {
+                eprintln!("Unable to load the persisted configuration: {:?}", e);
+                refresh::Snapshot::default()
+            }
+        },
+        None => refresh::Snapshot::default(),
     };
 
-    let app = web::App::new(cli.addr, docker_client.clone())?;
+    let (snapshot_tx, snapshot_rx) = watch::channel(initial);
+    let app = web::App::new(cli.addr, snapshot_rx.clone())?;
+
+    if let Some(output_dir) = cli.output_dir {
+        tokio::spawn(output::run(output_dir, cli.output_format, snapshot_rx.clone()));
+    }
+
+    let docker = provider::docker::DockerProvider::new(cli.docker).await?;
+
+    let mut providers: Vec<SharedProvider> = vec![Arc::new(Mutex::new(docker))];
+    if !cli.files.is_empty() {
+        providers.push(Arc::new(Mutex::new(provider::file::FileProvider::new(cli.files))));
+    }
 
-    while let Some(_event) = receiver.recv().await {
-        docker_client.lock().await.mark_dirty();
+    if let Some(state_file) = cli.state_file {
+        tokio::spawn(state::run(state_file, snapshot_rx));
     }
 
+    let changes = Arc::new(Notify::new());
+    let mut watchers = JoinSet::new();
+    for provider in &providers {
+        let provider = provider.clone();
+        let mut receiver = provider.lock().await.watch_changes();
+        let changes = changes.clone();
+        watchers.spawn(async move {
+            while receiver.recv().await.is_some() {
+                provider.lock().await.mark_dirty();
+                changes.notify_one();
+            }
+            eprintln!("Stopped watching changes from provider '{}'", provider.lock().await.name());
+        });
+    }
+
+    let refresher = tokio::spawn(refresh::run(
+        providers,
+        Duration::from_millis(cli.debounce_ms),
+        changes,
+        snapshot_tx,
+    ));
+
+    while watchers.join_next().await.is_some() {}
+
+    refresher.abort();
     app.shutdown();
 
     Ok(())
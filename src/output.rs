@@ -0,0 +1,104 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use serde_json::Value;
+use tokio::sync::watch;
+use yaml_rust2::yaml::Hash;
+use yaml_rust2::{Yaml, YamlEmitter};
+
+use crate::refresh::Snapshot;
+use crate::target::traefik::TraefikConfig;
+
+const OUTPUT_NAME: &str = "traefik-exposer";
+
+/// Format of the configuration written for Traefik's file provider.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    #[default]
+    Yaml,
+    Toml,
+}
+
+impl OutputFormat {
+    fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Yaml => "yaml",
+            OutputFormat::Toml => "toml",
+        }
+    }
+
+    fn render(&self, config: &TraefikConfig) -> anyhow::Result<String> {
+        match self {
+            OutputFormat::Yaml => {
+                let mut out = String::new();
+                YamlEmitter::new(&mut out).dump(&to_yaml(serde_json::to_value(config)?))?;
+                out.push('\n');
+                Ok(out)
+            }
+            OutputFormat::Toml => Ok(toml::to_string(config)?),
+        }
+    }
+}
+
+fn to_yaml(value: Value) -> Yaml {
+    match value {
+        Value::Null => Yaml::Null,
+        Value::Bool(b) => Yaml::Boolean(b),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => Yaml::Integer(i),
+            None => Yaml::Real(n.to_string()),
+        },
+        Value::String(s) => Yaml::String(s),
+        Value::Array(values) => Yaml::Array(values.into_iter().map(to_yaml).collect()),
+        Value::Object(map) => {
+            let mut hash = Hash::new();
+            for (k, v) in map {
+                hash.insert(Yaml::String(k), to_yaml(v));
+            }
+            Yaml::Hash(hash)
+        }
+    }
+}
+
+/// Replaces `path` with `contents` through a rename, so readers never see a
+/// partially written file. The temporary file has no configuration extension
+/// for Traefik to pick up.
+pub async fn write_atomically(path: &Path, contents: &str) -> anyhow::Result<()> {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let tmp = path.with_file_name(format!(".{}.tmp", name));
+    tokio::fs::write(&tmp, contents)
+        .await
+        .with_context(|| format!("failed to write {}", tmp.display()))?;
+    tokio::fs::rename(&tmp, path)
+        .await
+        .with_context(|| format!("failed to replace {}", path.display()))
+}
+
+/// Writes every new snapshot to `<dir>/traefik-exposer.<format>`, for a
+/// Traefik file provider watching `dir`.
+pub async fn run(dir: PathBuf, format: OutputFormat, mut snapshot: watch::Receiver<Snapshot>) {
+    let path = dir.join(format!("{}.{}", OUTPUT_NAME, format.extension()));
+    let mut written = None;
+
+    loop {
+        let current = snapshot.borrow_and_update().current.clone();
+        if let Some(current) = current {
+            match format.render(&current.config) {
+                // Unchanged rebuilds would only make Traefik reload for nothing.
+                Ok(contents) if written.as_ref() == Some(&contents) => {}
+                Ok(contents) => match write_atomically(&path, &contents).await {
+                    Ok(()) => {
+                        println!("Wrote configuration to {}", path.display());
+                        written = Some(contents);
+                    }
+                    Err(e) => eprintln!("Unable to write the configuration: {:?}", e),
+                },
+                Err(e) => eprintln!("Unable to render the configuration as {:?}: {:?}", format, e),
+            }
+        }
+
+        if snapshot.changed().await.is_err() {
+            return;
+        }
+    }
+}
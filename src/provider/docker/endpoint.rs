@@ -0,0 +1,581 @@
+use anyhow::{bail, Context};
+use bollard::{Docker, API_DEFAULT_VERSION};
+use clap::ValueEnum;
+use http_body_util::{BodyExt, Empty};
+use hyper::body::Bytes;
+use hyper_util::rt::TokioIo;
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{TcpStream, UnixStream};
+use tokio::process::{Child, Command};
+use tokio::sync::watch;
+use tokio_rustls::rustls::pki_types::ServerName;
+use tokio_rustls::rustls::{ClientConfig, RootCertStore};
+use tokio_rustls::TlsConnector;
+
+use super::{BackendMode, ContainerInfo};
+
+const TIMEOUT_SECS: u64 = 120;
+
+const DEFAULT_REMOTE_SOCKET: &str = "/var/run/docker.sock";
+
+const ROOTFUL_PODMAN_SOCKET: &str = "/run/podman/podman.sock";
+
+/// A `--docker-endpoint` value: `<scheme>://<target>[?key=value&...]`.
+///
+/// Supported schemes are `unix`, `tcp` (or `http`/`https`) and `ssh`. Known
+/// parameters are `name`, `address` (the host's address as seen from Traefik),
+/// `backend`, `tls`, `ca`, `cert`, `key`, `cert_path` and `socket` (remote
+/// socket for `ssh`).
+#[derive(Debug, Clone)]
+pub struct EndpointSpec {
+    scheme: String,
+    target: String,
+    params: HashMap<String, String>,
+}
+
+impl FromStr for EndpointSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (url, query) = s.split_once('?').unwrap_or((s, ""));
+        let (scheme, target) = url
+            .split_once("://")
+            .ok_or_else(|| format!("'{}' is not a <scheme>://<address> endpoint", s))?;
+
+        let params = query
+            .split('&')
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| {
+                let (k, v) = pair.split_once('=').unwrap_or((pair, "true"));
+                (k.to_string(), v.to_string())
+            })
+            .collect();
+
+        Ok(EndpointSpec {
+            scheme: scheme.to_string(),
+            target: target.to_string(),
+            params,
+        })
+    }
+}
+
+impl EndpointSpec {
+    /// Host part of the target, without user or port.
+    fn host(&self) -> &str {
+        let host = self.target.rsplit_once('@').map(|(_, h)| h).unwrap_or(&self.target);
+        let host = host.split('/').next().unwrap_or_default();
+        match host.rsplit_once(':') {
+            Some((h, port)) if port.chars().all(|c| c.is_ascii_digit()) => h,
+            _ => host,
+        }
+    }
+
+    fn param(&self, key: &str) -> Option<&String> {
+        self.params.get(key)
+    }
+
+    fn tls_files(&self) -> Option<(PathBuf, PathBuf, PathBuf)> {
+        let wants_tls = self.scheme == "https"
+            || self.param("tls").is_some_and(|v| v == "true")
+            || self.param("cert_path").is_some()
+            || self.param("cert").is_some();
+        if !wants_tls {
+            return None;
+        }
+
+        let cert_path = self
+            .param("cert_path")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("DOCKER_CERT_PATH").map(PathBuf::from))
+            .unwrap_or_default();
+        let file = |key: &str| {
+            self.param(key)
+                .map(PathBuf::from)
+                .unwrap_or_else(|| cert_path.join(format!("{}.pem", key)))
+        };
+
+        Some((file("key"), file("cert"), file("ca")))
+    }
+}
+
+/// A Docker daemon containers are listed from.
+pub struct DockerEndpoint {
+    pub name: String,
+    /// `None` until the socket of a `unix://` or `ssh://` endpoint shows up.
+    client: watch::Sender<Option<Docker>>,
+    transport: Transport,
+    /// Address Traefik reaches this host on, used for `host` backends.
+    pub address: Option<String>,
+    /// Backend mode for containers of this host, when it differs from the
+    /// global one.
+    pub backend: Option<BackendMode>,
+    /// Containers seen on the last successful listing, served while the host
+    /// is unreachable.
+    pub last_containers: Option<Vec<ContainerInfo>>,
+    /// Tunnel of `ssh://` endpoints, the client talks to its local socket.
+    tunnel: Option<SshTunnel>,
+}
+
+/// Podman socket to use when there is no `DOCKER_HOST` and no Docker socket,
+/// preferring the rootless one of the current user.
+fn local_podman_socket() -> Option<PathBuf> {
+    if std::env::var_os("DOCKER_HOST").is_some() || Path::new(DEFAULT_REMOTE_SOCKET).exists() {
+        return None;
+    }
+
+    let rootless = std::env::var_os("XDG_RUNTIME_DIR").map(|dir| PathBuf::from(dir).join("podman/podman.sock"));
+    rootless
+        .into_iter()
+        .chain(std::iter::once(PathBuf::from(ROOTFUL_PODMAN_SOCKET)))
+        .find(|socket| socket.exists())
+}
+
+/// Transport to the daemon of `DOCKER_HOST`, chosen the same way as the
+/// client of bollard's `connect_with_defaults`.
+fn local_transport(docker_host: Option<&str>, tls_verify: bool, cert_path: &Path) -> anyhow::Result<Transport> {
+    let Some(docker_host) = docker_host else {
+        return Ok(Transport::Unix(PathBuf::from(DEFAULT_REMOTE_SOCKET)));
+    };
+    let spec: EndpointSpec = docker_host
+        .parse()
+        .map_err(|e| anyhow::anyhow!("invalid DOCKER_HOST: {}", e))?;
+    let tls = || Transport::Tls {
+        addr: spec.target.clone(),
+        host: spec.host().to_string(),
+        key: cert_path.join("key.pem"),
+        cert: cert_path.join("cert.pem"),
+        ca: cert_path.join("ca.pem"),
+    };
+    Ok(match spec.scheme.as_str() {
+        "unix" => Transport::Unix(PathBuf::from(&spec.target)),
+        "tcp" | "http" if tls_verify => tls(),
+        "tcp" | "http" => Transport::Tcp(spec.target.clone()),
+        "https" => tls(),
+        scheme => bail!("unsupported DOCKER_HOST scheme '{}' in '{}'", scheme, docker_host),
+    })
+}
+
+/// Lowers the API version to the one the daemon supports, Podman and older
+/// Docker daemons reject requests for newer versions.
+async fn negotiate(name: &str, client: Docker) -> Docker {
+    match client.clone().negotiate_version().await {
+        Ok(client) => client,
+        Err(e) => {
+            println!("WARNING: unable to negotiate the API version of docker endpoint '{}': {}", name, e);
+            client
+        }
+    }
+}
+
+impl DockerEndpoint {
+    /// The endpoint of `DOCKER_HOST`, or of the local Docker (or Podman)
+    /// socket. Sockets are connected to on the first listing, so a daemon
+    /// that is not running yet is only unreachable.
+    pub async fn local() -> anyhow::Result<Self> {
+        let transport = match local_podman_socket() {
+            Some(socket) => {
+                println!("No docker socket found, using podman socket {}", socket.display());
+                Transport::Unix(socket)
+            }
+            None => {
+                let cert_path = std::env::var_os("DOCKER_CERT_PATH")
+                    .map(PathBuf::from)
+                    .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".docker")))
+                    .unwrap_or_default();
+                local_transport(
+                    std::env::var("DOCKER_HOST").ok().as_deref(),
+                    std::env::var_os("DOCKER_TLS_VERIFY").is_some(),
+                    &cert_path,
+                )?
+            }
+        };
+        let client = match transport {
+            Transport::Unix(_) => None,
+            _ => Some(negotiate("local", Docker::connect_with_defaults()?).await),
+        };
+
+        Ok(DockerEndpoint {
+            name: "local".to_string(),
+            client: watch::Sender::new(client),
+            transport,
+            address: None,
+            backend: None,
+            last_containers: None,
+            tunnel: None,
+        })
+    }
+
+    pub async fn connect(spec: &EndpointSpec) -> anyhow::Result<Self> {
+        // Sockets are named after their path, so that several of them get
+        // distinct qualified names.
+        let name = spec.param("name").cloned().unwrap_or_else(|| match spec.scheme.as_str() {
+            "unix" => spec.target.trim_start_matches('/').to_string(),
+            _ => spec.host().to_string(),
+        });
+
+        let address = spec.param("address").cloned().or_else(|| match spec.scheme.as_str() {
+            "unix" => None,
+            _ => Some(spec.host().to_string()),
+        });
+
+        let backend = match spec.param("backend") {
+            Some(mode) => Some(
+                BackendMode::from_str(mode, true)
+                    .map_err(|e| anyhow::anyhow!("invalid backend for endpoint '{}': {}", name, e))?,
+            ),
+            // Container IPs of remote hosts are usually not reachable.
+            None if address.is_some() => Some(BackendMode::Host),
+            None => None,
+        };
+
+        let mut endpoint = DockerEndpoint {
+            name,
+            client: watch::Sender::new(None),
+            transport: Transport::Unix(PathBuf::from(&spec.target)),
+            address,
+            backend,
+            last_containers: None,
+            tunnel: None,
+        };
+        match spec.scheme.as_str() {
+            "unix" => {}
+            "tcp" | "http" | "https" => {
+                let client = match spec.tls_files() {
+                    Some((key, cert, ca)) => {
+                        let client =
+                            Docker::connect_with_ssl(&spec.target, &key, &cert, &ca, TIMEOUT_SECS, API_DEFAULT_VERSION)?;
+                        endpoint.transport = Transport::Tls {
+                            addr: spec.target.clone(),
+                            host: spec.host().to_string(),
+                            key,
+                            cert,
+                            ca,
+                        };
+                        client
+                    }
+                    None => {
+                        endpoint.transport = Transport::Tcp(spec.target.clone());
+                        Docker::connect_with_http(&spec.target, TIMEOUT_SECS, API_DEFAULT_VERSION)?
+                    }
+                };
+                let client = negotiate(&endpoint.name, client).await;
+                println!("Connected to docker endpoint '{}'", endpoint.name);
+                endpoint.client.send_replace(Some(client));
+            }
+            "ssh" => {
+                let remote_socket = spec
+                    .param("socket")
+                    .map(String::as_str)
+                    .unwrap_or(DEFAULT_REMOTE_SOCKET);
+                let tunnel = SshTunnel::new(&endpoint.name, &spec.target, remote_socket);
+                endpoint.transport = Transport::Unix(tunnel.local_socket.clone());
+                endpoint.tunnel = Some(tunnel);
+            }
+            scheme => bail!("unsupported docker endpoint scheme '{}'", scheme),
+        }
+
+        // The host may only be down for now, it is retried on every listing.
+        if let Err(e) = endpoint.reconnect().await {
+            println!("WARNING: unable to connect to docker endpoint '{}': {:#}", endpoint.name, e);
+        }
+
+        Ok(endpoint)
+    }
+
+    /// The client of the endpoint, once connected.
+    pub fn client(&self) -> anyhow::Result<Docker> {
+        self.client
+            .borrow()
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("not connected"))
+    }
+
+    /// Sends a GET request for an API `path` bollard has no method for, and
+    /// decodes the JSON response.
+    pub async fn get<T: DeserializeOwned>(&self, path: &str) -> anyhow::Result<T> {
+        let version = self.client()?.client_version();
+        let uri = format!("/v{}.{}{}", version.major_version, version.minor_version, path);
+        let body = tokio::time::timeout(Duration::from_secs(TIMEOUT_SECS), self.transport.get(&uri))
+            .await
+            .with_context(|| format!("timed out requesting {}", path))??;
+        serde_json::from_slice(&body).with_context(|| format!("invalid response to {}", path))
+    }
+
+    /// Follows the client of the endpoint, for watchers started before it
+    /// connected.
+    pub fn subscribe(&self) -> watch::Receiver<Option<Docker>> {
+        self.client.subscribe()
+    }
+
+    /// Opens the ssh tunnel of the endpoint again if it closed, and connects
+    /// to its socket if that didn't happen yet.
+    pub async fn reconnect(&mut self) -> anyhow::Result<()> {
+        if let Some(ref mut tunnel) = self.tunnel {
+            tunnel.open().await?;
+        }
+        if self.client.borrow().is_some() {
+            return Ok(());
+        }
+        if let Transport::Unix(ref socket) = self.transport {
+            let client = Docker::connect_with_unix(&socket.to_string_lossy(), TIMEOUT_SECS, API_DEFAULT_VERSION)?;
+            let client = negotiate(&self.name, client).await;
+            println!("Connected to docker endpoint '{}'", self.name);
+            self.client.send_replace(Some(client));
+        }
+        Ok(())
+    }
+}
+
+/// How the daemon of an endpoint is reached, for the requests that are not
+/// sent through bollard.
+enum Transport {
+    Unix(PathBuf),
+    Tcp(String),
+    Tls {
+        addr: String,
+        host: String,
+        key: PathBuf,
+        cert: PathBuf,
+        ca: PathBuf,
+    },
+}
+
+impl Transport {
+    async fn get(&self, uri: &str) -> anyhow::Result<Bytes> {
+        match self {
+            Transport::Unix(socket) => send_get(UnixStream::connect(socket).await?, uri).await,
+            Transport::Tcp(addr) => send_get(TcpStream::connect(addr).await?, uri).await,
+            Transport::Tls {
+                addr,
+                host,
+                key,
+                cert,
+                ca,
+            } => {
+                let connector = TlsConnector::from(Arc::new(tls_config(key, cert, ca)?));
+                let server_name = ServerName::try_from(host.clone())?;
+                let stream = connector.connect(server_name, TcpStream::connect(addr).await?).await?;
+                send_get(stream, uri).await
+            }
+        }
+    }
+}
+
+/// Client configuration with the certificates of a `tcp://...?tls` endpoint,
+/// as the docker CLI uses them.
+fn tls_config(key: &Path, cert: &Path, ca: &Path) -> anyhow::Result<ClientConfig> {
+    let read = |path: &Path| std::fs::read(path).with_context(|| format!("failed to read {}", path.display()));
+
+    let mut roots = RootCertStore::empty();
+    for ca in rustls_pemfile::certs(&mut read(ca)?.as_slice()) {
+        roots.add(ca?)?;
+    }
+    let certs = rustls_pemfile::certs(&mut read(cert)?.as_slice()).collect::<Result<Vec<_>, _>>()?;
+    let key = rustls_pemfile::private_key(&mut read(key)?.as_slice())?
+        .with_context(|| format!("no private key in {}", key.display()))?;
+
+    Ok(ClientConfig::builder()
+        .with_root_certificates(roots)
+        .with_client_auth_cert(certs, key)?)
+}
+
+/// Sends a single GET request over `stream` and returns the response body.
+async fn send_get<S>(stream: S, uri: &str) -> anyhow::Result<Bytes>
+where
+    S: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+{
+    let (mut sender, connection) = hyper::client::conn::http1::handshake(TokioIo::new(stream)).await?;
+    tokio::spawn(connection);
+
+    let request = hyper::Request::get(uri)
+        .header(hyper::header::HOST, "docker")
+        .body(Empty::<Bytes>::new())?;
+    let response = sender.send_request(request).await?;
+    let status = response.status();
+    let body = response.into_body().collect().await?.to_bytes();
+    if !status.is_success() {
+        bail!("{}: {}", status, String::from_utf8_lossy(&body).trim());
+    }
+    Ok(body)
+}
+
+/// Forwards the remote Docker socket to a local one through `ssh`, the same
+/// way the docker CLI handles `ssh://` hosts.
+struct SshTunnel {
+    destination: String,
+    port: Option<String>,
+    remote_socket: String,
+    local_socket: PathBuf,
+    child: Option<Child>,
+}
+
+impl SshTunnel {
+    fn new(name: &str, target: &str, remote_socket: &str) -> Self {
+        let (destination, port) = match target.rsplit_once(':') {
+            Some((destination, port)) if port.chars().all(|c| c.is_ascii_digit()) => {
+                (destination, Some(port.to_string()))
+            }
+            _ => (target, None),
+        };
+
+        let local_socket = std::env::temp_dir().join(format!(
+            "traefik-exposer-{}-{}.sock",
+            std::process::id(),
+            name.replace(['/', ':', '@'], "_")
+        ));
+
+        SshTunnel {
+            destination: destination.to_string(),
+            port,
+            remote_socket: remote_socket.to_string(),
+            local_socket,
+            child: None,
+        }
+    }
+
+    /// Starts `ssh` unless it is still running, and waits for the local
+    /// socket to show up.
+    async fn open(&mut self) -> anyhow::Result<()> {
+        if let Some(ref mut child) = self.child {
+            if child.try_wait()?.is_none() {
+                return Ok(());
+            }
+        }
+        self.child = None;
+        let _ = std::fs::remove_file(&self.local_socket);
+
+        let mut command = Command::new("ssh");
+        command
+            .args(["-nNT", "-o", "ExitOnForwardFailure=yes", "-o", "StreamLocalBindUnlink=yes"])
+            .arg("-L")
+            .arg(format!("{}:{}", self.local_socket.display(), self.remote_socket));
+        if let Some(ref port) = self.port {
+            command.args(["-p", port]);
+        }
+        command.arg(&self.destination).kill_on_drop(true);
+
+        let mut child = command
+            .spawn()
+            .with_context(|| format!("failed to start ssh to {}", self.destination))?;
+
+        for _ in 0..100 {
+            if self.local_socket.exists() {
+                self.child = Some(child);
+                return Ok(());
+            }
+            if let Some(status) = child.try_wait()? {
+                bail!("ssh to {} exited with {}", self.destination, status);
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+
+        bail!("timed out waiting for the ssh tunnel to {}", self.destination)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec(s: &str) -> EndpointSpec {
+        s.parse().unwrap()
+    }
+
+    #[tokio::test]
+    async fn unix_endpoint() {
+        let spec = spec("unix:///var/run/docker.sock");
+        assert_eq!(spec.scheme, "unix");
+        assert_eq!(spec.target, "/var/run/docker.sock");
+        assert!(spec.tls_files().is_none());
+
+        let endpoint = DockerEndpoint::connect(&spec).await.unwrap();
+        assert_eq!(endpoint.name, "var/run/docker.sock");
+        assert_eq!(endpoint.address, None);
+        assert_eq!(endpoint.backend, None);
+        assert!(endpoint.client().is_err());
+    }
+
+    #[test]
+    fn tcp_endpoint() {
+        let tls = spec("tcp://10.0.0.2:2376?tls&cert_path=/certs&name=web2");
+        assert_eq!(tls.target, "10.0.0.2:2376");
+        assert_eq!(tls.host(), "10.0.0.2");
+        assert_eq!(tls.param("name").map(String::as_str), Some("web2"));
+        assert_eq!(tls.param("tls").map(String::as_str), Some("true"));
+        assert_eq!(
+            tls.tls_files(),
+            Some((
+                PathBuf::from("/certs/key.pem"),
+                PathBuf::from("/certs/cert.pem"),
+                PathBuf::from("/certs/ca.pem")
+            ))
+        );
+
+        let custom_ca = spec("tcp://10.0.0.2:2376?cert_path=/certs&ca=/etc/ca.pem");
+        assert_eq!(custom_ca.tls_files().map(|(_, _, ca)| ca), Some(PathBuf::from("/etc/ca.pem")));
+        assert!(spec("tcp://10.0.0.2:2375").tls_files().is_none());
+    }
+
+    #[tokio::test]
+    async fn ssh_endpoint() {
+        let spec = spec("ssh://deploy@10.0.0.3:22");
+        assert_eq!(spec.host(), "10.0.0.3");
+
+        let endpoint = DockerEndpoint::connect(&spec).await.unwrap();
+        assert_eq!(endpoint.name, "10.0.0.3");
+        assert_eq!(endpoint.address.as_deref(), Some("10.0.0.3"));
+        assert_eq!(endpoint.backend, Some(BackendMode::Host));
+        let tunnel = endpoint.tunnel.as_ref().unwrap();
+        assert_eq!(tunnel.destination, "deploy@10.0.0.3");
+        assert_eq!(tunnel.port.as_deref(), Some("22"));
+        assert_eq!(tunnel.remote_socket, DEFAULT_REMOTE_SOCKET);
+
+        let tunnel = SshTunnel::new("h", "h.example.com", "/run/user/1000/docker.sock");
+        assert_eq!(tunnel.destination, "h.example.com");
+        assert_eq!(tunnel.port, None);
+        assert_eq!(tunnel.remote_socket, "/run/user/1000/docker.sock");
+    }
+
+    #[tokio::test]
+    async fn invalid_endpoints() {
+        assert!("10.0.0.2:2376".parse::<EndpointSpec>().is_err());
+
+        let err = DockerEndpoint::connect(&spec("ftp://10.0.0.2")).await.err().unwrap();
+        assert!(err.to_string().contains("unsupported docker endpoint scheme 'ftp'"), "{}", err);
+        let err = DockerEndpoint::connect(&spec("ssh://10.0.0.2?backend=nope")).await.err().unwrap();
+        assert!(err.to_string().contains("invalid backend"), "{}", err);
+    }
+
+    #[test]
+    fn local_transports() {
+        let certs = Path::new("/certs");
+        let transport = |host: Option<&str>, tls_verify| local_transport(host, tls_verify, certs).unwrap();
+
+        assert!(matches!(transport(None, false), Transport::Unix(socket) if socket == Path::new(DEFAULT_REMOTE_SOCKET)));
+        assert!(matches!(
+            transport(Some("unix:///run/user/1000/docker.sock"), false),
+            Transport::Unix(socket) if socket == Path::new("/run/user/1000/docker.sock")
+        ));
+        assert!(matches!(transport(Some("tcp://10.0.0.2:2375"), false), Transport::Tcp(addr) if addr == "10.0.0.2:2375"));
+        for (host, tls_verify) in [("tcp://10.0.0.2:2376", true), ("https://10.0.0.2:2376", false)] {
+            match transport(Some(host), tls_verify) {
+                Transport::Tls { addr, host, ca, .. } => {
+                    assert_eq!(addr, "10.0.0.2:2376");
+                    assert_eq!(host, "10.0.0.2");
+                    assert_eq!(ca, Path::new("/certs/ca.pem"));
+                }
+                _ => panic!("{} is not reached through TLS", host),
+            }
+        }
+
+        let err = local_transport(Some("ssh://deploy@10.0.0.3"), false, certs).err().unwrap();
+        assert!(err.to_string().contains("unsupported DOCKER_HOST scheme 'ssh'"), "{}", err);
+        assert!(local_transport(Some("10.0.0.2:2375"), false, certs).is_err());
+    }
+}
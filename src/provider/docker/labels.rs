@@ -0,0 +1,657 @@
+use serde::de::{self, DeserializeOwned, IntoDeserializer, Visitor};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Error produced when a set of labels cannot be decoded into the requested type.
+#[derive(Debug)]
+pub struct DecodeError {
+    message: String,
+    /// Address of the `false` value found where a section was expected.
+    disabled: Option<usize>,
+}
+
+impl DecodeError {
+    fn new(message: String) -> Self {
+        DecodeError {
+            message,
+            disabled: None,
+        }
+    }
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+impl de::Error for DecodeError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        DecodeError::new(msg.to_string())
+    }
+}
+
+/// Tree built from flat, dotted label keys.
+///
+/// `basicAuth.users=a,b` becomes a map with a `basicAuth` entry holding a map
+/// with a `users` value. Bracketed indexes (`domains[0].main`) become map
+/// entries keyed by the index, which are decoded as sequences.
+#[derive(Debug, Clone)]
+pub enum LabelNode {
+    Value(String),
+    Map(BTreeMap<String, LabelNode>),
+}
+
+impl Default for LabelNode {
+    fn default() -> Self {
+        LabelNode::Map(BTreeMap::new())
+    }
+}
+
+static EMPTY: BTreeMap<String, LabelNode> = BTreeMap::new();
+
+fn split_key(key: &str) -> Vec<String> {
+    let mut segments = Vec::new();
+    for part in key.split('.') {
+        match part.find('[') {
+            Some(idx) if part.ends_with(']') => {
+                segments.push(part[..idx].to_string());
+                segments.push(part[idx + 1..part.len() - 1].to_string());
+            }
+            _ => segments.push(part.to_string()),
+        }
+    }
+    segments
+}
+
+impl LabelNode {
+    pub fn from_labels<'a, I>(labels: I) -> Result<Self, DecodeError>
+    where
+        I: IntoIterator<Item = (&'a str, &'a str)>,
+    {
+        let mut root = LabelNode::default();
+        for (key, value) in labels {
+            root.insert(key, value)?;
+        }
+        Ok(root)
+    }
+
+    pub fn insert(&mut self, key: &str, value: &str) -> Result<(), DecodeError> {
+        let conflict = || DecodeError::new(format!("label '{}' conflicts with another label", key));
+
+        let mut node = self;
+        for segment in split_key(key) {
+            // `tls=true` next to `tls.certResolver=...`: the children already
+            // enable the section.
+            if node.is_enabled_flag() {
+                *node = LabelNode::default();
+            }
+            node = match node {
+                LabelNode::Map(children) => children.entry(segment).or_default(),
+                LabelNode::Value(_) => return Err(conflict()),
+            };
+        }
+
+        match node {
+            LabelNode::Map(children) if children.is_empty() => {
+                *node = LabelNode::Value(value.to_string());
+                Ok(())
+            }
+            LabelNode::Map(_) if value.trim().eq_ignore_ascii_case("true") => Ok(()),
+            _ => Err(conflict()),
+        }
+    }
+
+    fn is_enabled_flag(&self) -> bool {
+        matches!(self, LabelNode::Value(s) if s.trim().eq_ignore_ascii_case("true"))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        matches!(self, LabelNode::Map(children) if children.is_empty())
+    }
+
+    pub fn children(&self) -> impl Iterator<Item = (&String, &LabelNode)> {
+        let children = match self {
+            LabelNode::Map(children) => Some(children.iter()),
+            LabelNode::Value(_) => None,
+        };
+        children.into_iter().flatten()
+    }
+
+    /// Decodes the tree into a `T`. A `section=false` label disables an
+    /// optional section, as if none of its labels were set.
+    pub fn decode<T: DeserializeOwned>(&self) -> Result<T, DecodeError> {
+        let mut tree = Cow::Borrowed(self);
+        loop {
+            match T::deserialize(tree.as_ref()) {
+                Err(DecodeError {
+                    disabled: Some(node),
+                    message,
+                }) => match tree.path_to(node) {
+                    Some(path) if !path.is_empty() => tree.to_mut().remove(&path),
+                    _ => return Err(DecodeError::new(message)),
+                },
+                result => return result,
+            }
+        }
+    }
+
+    /// Keys leading from this node to the node at `address`.
+    fn path_to(&self, address: usize) -> Option<Vec<String>> {
+        if std::ptr::eq(self, address as *const LabelNode) {
+            return Some(Vec::new());
+        }
+        self.children().find_map(|(key, child)| {
+            let mut path = child.path_to(address)?;
+            path.insert(0, key.clone());
+            Some(path)
+        })
+    }
+
+    fn remove(&mut self, path: &[String]) {
+        if let LabelNode::Map(children) = self {
+            match path {
+                [key] => {
+                    children.remove(key);
+                }
+                [key, rest @ ..] => {
+                    if let Some(child) = children.get_mut(key) {
+                        child.remove(rest);
+                    }
+                }
+                [] => {}
+            }
+        }
+    }
+
+    fn unexpected(&self) -> de::Unexpected<'_> {
+        match self {
+            LabelNode::Value(s) => de::Unexpected::Str(s),
+            LabelNode::Map(_) => de::Unexpected::Map,
+        }
+    }
+
+    fn sequence(&self) -> Result<Vec<Cow<'_, LabelNode>>, DecodeError> {
+        match self {
+            LabelNode::Value(s) if s.trim().is_empty() => Ok(Vec::new()),
+            LabelNode::Value(s) => Ok(s
+                .split(',')
+                .map(|item| Cow::Owned(LabelNode::Value(item.trim().to_string())))
+                .collect()),
+            LabelNode::Map(children) => {
+                let mut items = children
+                    .iter()
+                    .map(|(k, v)| {
+                        k.parse::<usize>()
+                            .map(|idx| (idx, Cow::Borrowed(v)))
+                            .map_err(|_| DecodeError::new(format!("expected a list index, found '{}'", k)))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                items.sort_by_key(|(idx, _)| *idx);
+                Ok(items.into_iter().map(|(_, v)| v).collect())
+            }
+        }
+    }
+}
+
+/// Labels are matched case-insensitively, like Traefik does, so map them back
+/// to the exact name serde expects.
+fn find_name(key: &str, names: &'static [&'static str]) -> Option<&'static str> {
+    names.iter().find(|name| name.eq_ignore_ascii_case(key)).copied()
+}
+
+fn canonical_name(key: &str, names: &'static [&'static str]) -> String {
+    find_name(key, names).unwrap_or(key).to_string()
+}
+
+macro_rules! deserialize_parsed {
+    ($($method:ident => $visit:ident),* $(,)?) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+                match self {
+                    LabelNode::Value(s) => match s.trim().parse() {
+                        Ok(v) => visitor.$visit(v),
+                        Err(_) => Err(de::Error::invalid_value(self.unexpected(), &visitor)),
+                    },
+                    LabelNode::Map(_) => Err(de::Error::invalid_type(self.unexpected(), &visitor)),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for &LabelNode {
+    type Error = DecodeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self {
+            LabelNode::Value(s) => visitor.visit_str(s),
+            LabelNode::Map(children) => visitor.visit_map(NodeMap::new(children, None)),
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self {
+            LabelNode::Value(s) => match s.trim().to_ascii_lowercase().as_str() {
+                "true" => visitor.visit_bool(true),
+                "false" => visitor.visit_bool(false),
+                _ => Err(de::Error::invalid_value(self.unexpected(), &visitor)),
+            },
+            LabelNode::Map(_) => Err(de::Error::invalid_type(self.unexpected(), &visitor)),
+        }
+    }
+
+    deserialize_parsed! {
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+        deserialize_char => visit_char,
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self {
+            LabelNode::Value(s) => visitor.visit_str(s),
+            LabelNode::Map(_) => Err(de::Error::invalid_type(self.unexpected(), &visitor)),
+        }
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_seq(NodeSeq {
+            items: self.sequence()?.into_iter(),
+        })
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, Self::Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self {
+            LabelNode::Map(children) => visitor.visit_map(NodeMap::new(children, None)),
+            LabelNode::Value(_) => Err(de::Error::invalid_type(self.unexpected(), &visitor)),
+        }
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        match self {
+            LabelNode::Map(children) => visitor.visit_map(NodeMap::new(children, Some(fields))),
+            // `section=true` enables a section with all of its defaults.
+            LabelNode::Value(s) if s.trim().eq_ignore_ascii_case("true") => {
+                visitor.visit_map(NodeMap::new(&EMPTY, Some(fields)))
+            }
+            // `section=false` disables it, `decode` drops the value and retries.
+            LabelNode::Value(s) if s.trim().eq_ignore_ascii_case("false") => Err(DecodeError {
+                disabled: Some(self as *const LabelNode as usize),
+                ..de::Error::invalid_type(self.unexpected(), &visitor)
+            }),
+            LabelNode::Value(_) => Err(de::Error::invalid_type(self.unexpected(), &visitor)),
+        }
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        match self {
+            LabelNode::Value(s) => visitor.visit_enum(NodeEnum {
+                variant: canonical_name(s.trim(), variants),
+                value: None,
+            }),
+            LabelNode::Map(children) if children.len() == 1 => {
+                let (variant, value) = children.iter().next().unwrap();
+                visitor.visit_enum(NodeEnum {
+                    variant: canonical_name(variant, variants),
+                    value: Some(value),
+                })
+            }
+            LabelNode::Map(children) => Err(DecodeError::new(format!(
+                "expected exactly one of {:?}, found {:?}",
+                variants,
+                children.keys().collect::<Vec<_>>()
+            ))),
+        }
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_unit()
+    }
+}
+
+struct NodeSeq<'a> {
+    items: std::vec::IntoIter<Cow<'a, LabelNode>>,
+}
+
+impl<'de> de::SeqAccess<'de> for NodeSeq<'_> {
+    type Error = DecodeError;
+
+    fn next_element_seed<T: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Self::Error> {
+        match self.items.next() {
+            Some(item) => seed.deserialize(item.as_ref()).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+struct NodeMap<'a> {
+    entries: std::collections::btree_map::Iter<'a, String, LabelNode>,
+    /// Fields of the struct being decoded, `None` for plain maps.
+    fields: Option<&'static [&'static str]>,
+    value: Option<&'a LabelNode>,
+}
+
+impl<'a> NodeMap<'a> {
+    fn new(children: &'a BTreeMap<String, LabelNode>, fields: Option<&'static [&'static str]>) -> Self {
+        NodeMap {
+            entries: children.iter(),
+            fields,
+            value: None,
+        }
+    }
+}
+
+impl<'de> de::MapAccess<'de> for NodeMap<'_> {
+    type Error = DecodeError;
+
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Self::Error> {
+        match self.entries.next() {
+            Some((key, value)) => {
+                self.value = Some(value);
+                let key = match self.fields {
+                    // Like Traefik, reject unknown fields instead of silently
+                    // ignoring a typo such as `tls.certResolvr`.
+                    Some(fields) => match find_name(key, fields) {
+                        Some(name) => name.to_string(),
+                        None => return Err(de::Error::unknown_field(key, fields)),
+                    },
+                    None => key.clone(),
+                };
+                seed.deserialize(key.into_deserializer()).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: de::DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Self::Error> {
+        match self.value.take() {
+            Some(value) => seed.deserialize(value),
+            None => Err(de::Error::custom("value requested before key")),
+        }
+    }
+}
+
+struct NodeEnum<'a> {
+    variant: String,
+    value: Option<&'a LabelNode>,
+}
+
+impl<'de, 'a> de::EnumAccess<'de> for NodeEnum<'a> {
+    type Error = DecodeError;
+    type Variant = Self;
+
+    fn variant_seed<V: de::DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self), Self::Error> {
+        let variant = seed.deserialize(self.variant.clone().into_deserializer())?;
+        Ok((variant, self))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for NodeEnum<'_> {
+    type Error = DecodeError;
+
+    fn unit_variant(self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: de::DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Self::Error> {
+        match self.value {
+            Some(value) => seed.deserialize(value),
+            None => seed.deserialize(&LabelNode::default()),
+        }
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, Self::Error> {
+        let empty = LabelNode::default();
+        de::Deserializer::deserialize_seq(self.value.unwrap_or(&empty), visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        let empty = LabelNode::default();
+        de::Deserializer::deserialize_struct(self.value.unwrap_or(&empty), "", fields, visitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    fn tree(labels: &[(&str, &str)]) -> Result<LabelNode, DecodeError> {
+        LabelNode::from_labels(labels.iter().copied())
+    }
+
+    #[derive(Debug, Default, Deserialize, PartialEq)]
+    #[serde(rename_all = "camelCase")]
+    struct Domain {
+        main: String,
+        #[serde(default)]
+        sans: Vec<String>,
+    }
+
+    #[derive(Debug, Default, Deserialize, PartialEq)]
+    #[serde(rename_all = "camelCase")]
+    struct Tls {
+        cert_resolver: Option<String>,
+        #[serde(default)]
+        domains: Vec<Domain>,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(rename_all = "camelCase")]
+    struct Router {
+        rule: String,
+        #[serde(default)]
+        entry_points: Vec<String>,
+        priority: Option<u32>,
+        weight: Option<f64>,
+        passthrough: Option<bool>,
+        tls: Option<Tls>,
+    }
+
+    #[test]
+    fn coerces_values() {
+        let router: Router = tree(&[
+            ("rule", "Host(`a`)"),
+            ("entryPoints", "web, websecure"),
+            ("priority", " 10 "),
+            ("weight", "0.5"),
+            ("passthrough", "TRUE"),
+        ])
+        .unwrap()
+        .decode()
+        .unwrap();
+        assert_eq!(router.rule, "Host(`a`)");
+        assert_eq!(router.entry_points, vec!["web", "websecure"]);
+        assert_eq!(router.priority, Some(10));
+        assert_eq!(router.weight, Some(0.5));
+        assert_eq!(router.passthrough, Some(true));
+        assert_eq!(router.tls, None);
+    }
+
+    #[test]
+    fn rejects_invalid_values() {
+        let labels = tree(&[("rule", "r"), ("priority", "high")]).unwrap();
+        assert!(labels.decode::<Router>().is_err());
+        let labels = tree(&[("rule", "r"), ("passthrough", "yes")]).unwrap();
+        assert!(labels.decode::<Router>().is_err());
+    }
+
+    #[test]
+    fn empty_list() {
+        let router: Router = tree(&[("rule", "r"), ("entryPoints", "")]).unwrap().decode().unwrap();
+        assert!(router.entry_points.is_empty());
+    }
+
+    #[test]
+    fn indexes() {
+        let tls: Tls = tree(&[
+            ("domains[10].main", "k.example"),
+            ("domains[2].main", "c.example"),
+            ("domains[2].sans", "*.c.example,d.example"),
+            ("domains[0].main", "a.example"),
+        ])
+        .unwrap()
+        .decode()
+        .unwrap();
+        let mains: Vec<_> = tls.domains.iter().map(|d| d.main.as_str()).collect();
+        assert_eq!(mains, vec!["a.example", "c.example", "k.example"]);
+        assert_eq!(tls.domains[1].sans, vec!["*.c.example", "d.example"]);
+
+        let labels = tree(&[("domains.first.main", "a.example")]).unwrap();
+        assert!(labels.decode::<Tls>().is_err());
+    }
+
+    #[test]
+    fn section_flag() {
+        let router: Router = tree(&[("rule", "r"), ("tls", "true")]).unwrap().decode().unwrap();
+        assert_eq!(router.tls, Some(Tls::default()));
+
+        // The flag is redundant next to the section's own labels, in any order.
+        for labels in [
+            [("tls", "true"), ("tls.certResolver", "le")],
+            [("tls.certResolver", "le"), ("tls", "true")],
+        ] {
+            let mut labels = labels.to_vec();
+            labels.push(("rule", "r"));
+            let router: Router = tree(&labels).unwrap().decode().unwrap();
+            assert_eq!(router.tls.unwrap().cert_resolver.as_deref(), Some("le"));
+        }
+    }
+
+    #[test]
+    fn disabled_section() {
+        let router: Router = tree(&[("rule", "r"), ("tls", "False"), ("passthrough", "false")])
+            .unwrap()
+            .decode()
+            .unwrap();
+        assert_eq!(router.tls, None);
+        assert_eq!(router.passthrough, Some(false));
+
+        let tls: Tls = tree(&[("domains[0].main", "a.example"), ("domains[1]", "false")])
+            .unwrap()
+            .decode()
+            .unwrap();
+        assert_eq!(tls.domains.len(), 1);
+
+        assert!(tree(&[("rule", "false")]).unwrap().decode::<Router>().is_ok());
+        assert!(LabelNode::Value("false".to_string()).decode::<Tls>().is_err());
+    }
+
+    #[test]
+    fn case_insensitive_fields() {
+        let router: Router = tree(&[
+            ("Rule", "r"),
+            ("entrypoints", "web"),
+            ("TLS.certresolver", "le"),
+        ])
+        .unwrap()
+        .decode()
+        .unwrap();
+        assert_eq!(router.rule, "r");
+        assert_eq!(router.entry_points, vec!["web"]);
+        assert_eq!(router.tls.unwrap().cert_resolver.as_deref(), Some("le"));
+    }
+
+    #[test]
+    fn unknown_fields() {
+        let labels = tree(&[("rule", "r"), ("tls.certResolvr", "le")]).unwrap();
+        let err = labels.decode::<Router>().unwrap_err().to_string();
+        assert!(err.contains("certResolvr"), "{}", err);
+
+        // Plain maps take any key.
+        let map: BTreeMap<String, String> = tree(&[("a", "1"), ("B", "2")]).unwrap().decode().unwrap();
+        assert_eq!(map.keys().collect::<Vec<_>>(), vec!["B", "a"]);
+    }
+
+    #[test]
+    fn conflicts() {
+        assert!(tree(&[("rule", "a"), ("rule", "b")]).is_err());
+        assert!(tree(&[("tls", "false"), ("tls.certResolver", "le")]).is_err());
+        assert!(tree(&[("tls.certResolver", "le"), ("tls", "false")]).is_err());
+        assert!(tree(&[("tls.certResolver", "le"), ("tls.certResolver.name", "x")]).is_err());
+    }
+}
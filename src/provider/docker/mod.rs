@@ -1,26 +1,234 @@
+use async_trait::async_trait;
+use clap::ValueEnum;
 use bollard::container::ListContainersOptions;
-use bollard::secret::EventMessage;
+use bollard::secret::{EndpointSettings, EventMessage, EventMessageTypeEnum, Port, PortTypeEnum};
 use bollard::system::EventsOptions;
 use futures::stream::StreamExt;
-use std::collections::HashMap;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::sync::atomic::AtomicBool;
+use std::time::Duration;
 use tokio::sync::mpsc::Receiver;
 use tokio::task;
+use tokio::time::{timeout_at, Instant};
 
+use crate::provider::Provider;
 use crate::target::traefik::{
-    HttpConfig, LoadBalancerConfig, RouterConfig, ServerConfig, ServiceConfig, TraefikConfig,
+    HealthCheckConfig, HttpConfig, LoadBalancerConfig, MiddlewareConfig, RouterConfig,
+    ServerConfig, ServiceConfig, TcpConfig, TcpLoadBalancerConfig, TcpMiddlewareConfig,
+    TcpRouterConfig, TcpServerConfig, TcpServiceConfig, TlsConfig, TraefikConfig, UdpConfig,
+    UdpLoadBalancerConfig, UdpRouterConfig, UdpServerConfig, UdpServiceConfig,
 };
 
+use endpoint::{DockerEndpoint, EndpointSpec};
+use labels::{DecodeError, LabelNode};
+use swarm::SwarmBackend;
+
+mod endpoint;
+mod labels;
+mod swarm;
+
 const LABEL_PREFIX: &str = "kasama.traefik-exposer.";
 
+const COMPOSE_PROJECT_LABEL: &str = "com.docker.compose.project";
+const COMPOSE_SERVICE_LABEL: &str = "com.docker.compose.service";
+
+/// Container and network events that can change which containers are
+/// published, or how they are reached.
+const CONTAINER_EVENTS: [&str; 8] = [
+    "start",
+    "stop",
+    "die",
+    "destroy",
+    "rename",
+    "connect",
+    "disconnect",
+    "health_status",
+];
+
+/// Service events that can change the published services in swarm mode.
+const SERVICE_EVENTS: [&str; 3] = ["create", "update", "remove"];
+
+const MIN_RECONNECT_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_RECONNECT_BACKOFF: Duration = Duration::from_secs(60);
+/// An event stream is only considered reconnected once it delivered an event
+/// or stayed open for this long.
+const MIN_STREAM_UPTIME: Duration = Duration::from_secs(10);
+
+/// Podman names for the Docker container events it names differently.
+fn podman_action(action: &str) -> Option<&'static str> {
+    match action {
+        "die" => Some("died"),
+        "destroy" => Some("remove"),
+        _ => None,
+    }
+}
+
+/// Translates the container events Podman names differently to their Docker
+/// names. Actions carrying details, e.g. `health_status: healthy`, are reduced
+/// to the action itself.
+fn event_action(event: &EventMessage) -> Option<&str> {
+    let action = event.action.as_deref()?.split(':').next().unwrap_or_default().trim();
+    let container = event.typ == Some(EventMessageTypeEnum::CONTAINER);
+    Some(match action {
+        "died" if container => "die",
+        "remove" if container => "destroy",
+        action => action,
+    })
+}
+
 fn label_key(name: &str) -> String {
     format!("{}{}", LABEL_PREFIX, name)
 }
 
+/// Builds a tree out of every label under `<LABEL_PREFIX><namespace>.`,
+/// matched regardless of case like the fields of the tree.
+fn label_tree(labels: &HashMap<String, String>, namespace: &str) -> Result<LabelNode, DecodeError> {
+    let prefix = label_key(&format!("{}.", namespace));
+    LabelNode::from_labels(labels.iter().filter_map(|(k, v)| {
+        k.get(..prefix.len())
+            .filter(|p| p.eq_ignore_ascii_case(&prefix))
+            .map(|_| (&k[prefix.len()..], v.as_str()))
+    }))
+}
+
+/// Whether containers are published depending on their Docker HEALTHCHECK.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum HealthPolicy {
+    /// Publish containers regardless of their health.
+    #[default]
+    Ignore,
+    /// Publish containers once they are healthy. Containers without a
+    /// healthcheck are always published.
+    RequireHealthy,
+    /// Publish containers right away, and drop them while they are unhealthy.
+    DropUnhealthy,
+}
+
+/// How backends are addressed in the generated configuration.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum BackendMode {
+    /// The container's IP and port, for a Traefik that shares its network.
+    #[default]
+    Container,
+    /// The Docker host's address and the port published for the container
+    /// port, for a Traefik running on another machine.
+    Host,
+}
+
+#[derive(Debug, Clone, clap::Args)]
+pub struct DockerOptions {
+    /// How the Docker HEALTHCHECK status affects which containers are published
+    #[clap(long, value_enum, default_value_t, env = "EXPOSER_HEALTH_POLICY")]
+    pub health_policy: HealthPolicy,
+
+    /// Docker network backend IPs are taken from. Can be overridden per
+    /// container with the `kasama.traefik-exposer.network` label
+    #[clap(long, env = "EXPOSER_NETWORK")]
+    pub network: Option<String>,
+
+    /// Which address backends are reached on. Can be overridden per container
+    /// with the `kasama.traefik-exposer.backend` label
+    #[clap(long, value_enum, default_value_t, env = "EXPOSER_BACKEND")]
+    pub backend: BackendMode,
+
+    /// Address of the Docker host as seen from Traefik, used for `host` backends
+    #[clap(long, env = "EXPOSER_HOST_ADDRESS")]
+    pub host_address: Option<String>,
+
+    /// Docker hosts to aggregate, e.g. `unix:///var/run/docker.sock`,
+    /// `tcp://10.0.0.2:2376?tls&cert_path=/certs&name=web2` or
+    /// `ssh://deploy@10.0.0.3`. Defaults to the local Docker daemon, or to
+    /// the Podman socket (rootless first) when there is none
+    #[clap(long = "docker-endpoint", env = "EXPOSER_DOCKER_ENDPOINTS", value_delimiter = ',')]
+    pub endpoints: Vec<EndpointSpec>,
+
+    /// Publish swarm services instead of containers. The docker endpoints
+    /// must be swarm managers
+    #[clap(long, env = "EXPOSER_SWARM")]
+    pub swarm: bool,
+
+    /// How swarm services are reached. Can be overridden per service with
+    /// the `kasama.traefik-exposer.swarm.backend` label
+    #[clap(long, value_enum, default_value_t, env = "EXPOSER_SWARM_BACKEND")]
+    pub swarm_backend: SwarmBackend,
+
+    /// Seconds between full resyncs in swarm mode, 0 to disable them. Tasks
+    /// that are restarted or rescheduled emit no service event, their new
+    /// addresses are only picked up this way with the `tasks` backend
+    #[clap(long, default_value = "30", env = "EXPOSER_SWARM_RESYNC_SECS")]
+    pub swarm_resync_secs: u64,
+
+    /// Docker events that refresh the configuration. Defaults to the
+    /// container and network events that affect it, or to the service events
+    /// in swarm mode
+    #[clap(long = "watch-event", env = "EXPOSER_WATCH_EVENTS", value_delimiter = ',')]
+    pub watch_events: Vec<String>,
+
+    /// Load balance the replicas of a docker compose service behind one
+    /// router and service, named `<project>-<service>`, as if they shared a
+    /// `kasama.traefik-exposer.service` label
+    #[clap(long, env = "EXPOSER_COMPOSE_REPLICAS")]
+    pub compose_replicas: bool,
+}
+
+/// What the event watchers of the docker endpoints report.
+#[derive(Debug)]
+pub enum DockerEvent {
+    /// A watched event.
+    Event(EventMessage),
+    /// The event stream of an endpoint dropped, the endpoint is likely
+    /// unreachable.
+    Disconnected,
+    /// The event stream of an endpoint was reconnected, and events may have
+    /// been missed while it was down.
+    Reconnected,
+}
+
 pub struct DockerProvider {
-    client: bollard::Docker,
+    endpoints: Vec<DockerEndpoint>,
+    options: DockerOptions,
     memory: Option<TraefikConfig>,
     dirty: AtomicBool,
+    /// Endpoints that could not be listed on the last listing, served from
+    /// their last known containers or left out.
+    unreachable: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HealthStatus {
+    /// The container has no healthcheck.
+    None,
+    Starting,
+    Healthy,
+    Unhealthy,
+}
+
+impl HealthStatus {
+    /// Parses the health out of the status Docker reports when listing
+    /// containers, e.g. `Up 5 minutes (healthy)` or `Up 2 seconds (health: starting)`.
+    fn from_status(status: &str) -> Self {
+        if status.contains("(unhealthy)") {
+            HealthStatus::Unhealthy
+        } else if status.contains("(healthy)") {
+            HealthStatus::Healthy
+        } else if status.contains("(health: starting)") {
+            HealthStatus::Starting
+        } else {
+            HealthStatus::None
+        }
+    }
+}
+
+impl HealthPolicy {
+    fn allows(&self, health: HealthStatus) -> bool {
+        match self {
+            HealthPolicy::Ignore => true,
+            HealthPolicy::RequireHealthy => matches!(health, HealthStatus::None | HealthStatus::Healthy),
+            HealthPolicy::DropUnhealthy => health != HealthStatus::Unhealthy,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -28,132 +236,1001 @@ pub struct ContainerInfo {
     name: String,
     ip: String,
     labels: HashMap<String, String>,
+    ports: Vec<Port>,
+    backend: BackendMode,
+    host_address: Option<String>,
+    /// Service this is a replica of: the swarm service of a task, or
+    /// `<project>-<service>` for containers started by docker compose when
+    /// `--compose-replicas` is set. Qualified like `name` when aggregating
+    /// hosts.
+    replica_of: Option<String>,
+    /// Endpoint the container was listed from, when aggregating hosts. The
+    /// routers, services and middlewares it defines through labels are
+    /// prefixed with it, so the same labels on two hosts don't collide.
+    label_scope: Option<String>,
+}
+
+/// A named router group, `kasama.traefik-exposer.routers.<group>.*`.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RouterLabels {
+    rule: String,
+    entry_points: Option<Vec<String>>,
+    #[serde(default)]
+    middlewares: Vec<String>,
+    service: Option<String>,
+    tls: Option<TlsConfig>,
+}
+
+/// A named service group, `kasama.traefik-exposer.services.<group>.*`.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ServiceLabels {
+    port: Option<u16>,
+    health_check: Option<HealthCheckConfig>,
+}
+
+fn load_balancer(url: String, weight: u32, health_check: Option<HealthCheckConfig>) -> ServiceConfig {
+    ServiceConfig::LoadBalancer(LoadBalancerConfig {
+        sticky: None,
+        servers: vec![ServerConfig {
+            url,
+            weight,
+            preserve_path: true,
+        }],
+        health_check,
+        pass_host_header: None,
+        response_forwarding: None,
+        servers_transport: None,
+    })
+}
+
+/// Merges the services of a replica into `into`: servers join the load
+/// balancer of the same name instead of replacing it.
+fn merge_replica_services<S: LoadBalanced>(into: &mut BTreeMap<String, S>, services: &mut BTreeMap<String, S>) {
+    services.retain(|name, service| {
+        match (into.get_mut(name).and_then(LoadBalanced::servers), service.servers()) {
+            (Some(existing), Some(servers)) => {
+                existing.append(servers);
+                false
+            }
+            _ => true,
+        }
+    });
+}
+
+/// Leaves out the entries another replica already defined the same way.
+fn dedup_replica_entries<T: PartialEq>(into: &BTreeMap<String, T>, entries: &mut BTreeMap<String, T>) {
+    entries.retain(|name, entry| into.get(name) != Some(entry));
+}
+
+/// First router of `routers` defined differently in `into`.
+fn conflicting_router<'a>(
+    into: &BTreeMap<String, RouterConfig>,
+    routers: &'a BTreeMap<String, RouterConfig>,
+) -> Option<&'a String> {
+    routers
+        .iter()
+        .find(|(name, router)| into.get(*name).is_some_and(|existing| existing != *router))
+        .map(|(name, _)| name)
+}
+
+/// Prefixes the entries named in `names` with `scope`.
+fn scope_entries<T>(entries: &mut BTreeMap<String, T>, names: &BTreeSet<String>, scope: &str) {
+    *entries = std::mem::take(entries)
+        .into_iter()
+        .map(|(name, entry)| match names.contains(&name) {
+            true => (format!("{}/{}", scope, name), entry),
+            false => (name, entry),
+        })
+        .collect();
+}
+
+/// Prefixes `reference` with `scope` when it is one of `names`, and leaves
+/// references to entries defined elsewhere alone.
+fn scope_reference(reference: &mut String, names: &BTreeSet<String>, scope: &str) {
+    if names.contains(reference.as_str()) {
+        *reference = format!("{}/{}", scope, reference);
+    }
+}
+
+/// Names of the routers, services and middlewares a container defines
+/// through labels.
+struct LabeledNames {
+    routers: BTreeSet<String>,
+    services: BTreeSet<String>,
+    middlewares: BTreeSet<String>,
+}
+
+impl LabeledNames {
+    fn new<R, S, M>(
+        routers: &BTreeMap<String, R>,
+        services: &BTreeMap<String, S>,
+        middlewares: &BTreeMap<String, M>,
+    ) -> Self {
+        LabeledNames {
+            routers: routers.keys().cloned().collect(),
+            services: services.keys().cloned().collect(),
+            middlewares: middlewares.keys().cloned().collect(),
+        }
+    }
+}
+
+/// Scopes the HTTP entries named in `labeled`, along with every reference
+/// to them.
+fn scope_http(http: &mut HttpConfig, labeled: &LabeledNames, scope: &str) {
+    let LabeledNames {
+        routers,
+        services,
+        middlewares,
+    } = labeled;
+    scope_entries(&mut http.routers, routers, scope);
+    scope_entries(&mut http.services, services, scope);
+    scope_entries(&mut http.middlewares, middlewares, scope);
+
+    for router in http.routers.values_mut() {
+        scope_reference(&mut router.service, services, scope);
+        for middleware in &mut router.middlewares {
+            scope_reference(middleware, middlewares, scope);
+        }
+    }
+    for service in http.services.values_mut() {
+        match service {
+            ServiceConfig::Failover(failover) => {
+                scope_reference(&mut failover.service, services, scope);
+                scope_reference(&mut failover.fallback, services, scope);
+            }
+            ServiceConfig::Mirroring(mirroring) => {
+                scope_reference(&mut mirroring.service, services, scope);
+                for mirror in &mut mirroring.mirrors {
+                    scope_reference(&mut mirror.name, services, scope);
+                }
+            }
+            ServiceConfig::Weighted(weighted) => {
+                for weighted in &mut weighted.services {
+                    scope_reference(&mut weighted.name, services, scope);
+                }
+            }
+            ServiceConfig::LoadBalancer(_) => {}
+        }
+    }
+    for middleware in http.middlewares.values_mut() {
+        if let MiddlewareConfig::Chain(chain) = middleware {
+            for middleware in &mut chain.middlewares {
+                scope_reference(middleware, middlewares, scope);
+            }
+        }
+    }
+}
+
+/// Scopes the TCP entries named in `labeled`, along with every reference to
+/// them.
+fn scope_tcp(tcp: &mut TcpConfig, labeled: &LabeledNames, scope: &str) {
+    scope_entries(&mut tcp.routers, &labeled.routers, scope);
+    scope_entries(&mut tcp.services, &labeled.services, scope);
+    scope_entries(&mut tcp.middlewares, &labeled.middlewares, scope);
+
+    for router in tcp.routers.values_mut() {
+        scope_reference(&mut router.service, &labeled.services, scope);
+        for middleware in &mut router.middlewares {
+            scope_reference(middleware, &labeled.middlewares, scope);
+        }
+    }
+    for service in tcp.services.values_mut() {
+        if let TcpServiceConfig::Weighted(weighted) = service {
+            for weighted in &mut weighted.services {
+                scope_reference(&mut weighted.name, &labeled.services, scope);
+            }
+        }
+    }
+}
+
+/// Scopes the UDP entries named in `labeled`, along with every reference to
+/// them.
+fn scope_udp(udp: &mut UdpConfig, labeled: &LabeledNames, scope: &str) {
+    scope_entries(&mut udp.routers, &labeled.routers, scope);
+    scope_entries(&mut udp.services, &labeled.services, scope);
+
+    for router in udp.routers.values_mut() {
+        scope_reference(&mut router.service, &labeled.services, scope);
+    }
+    for service in udp.services.values_mut() {
+        if let UdpServiceConfig::Weighted(weighted) = service {
+            for weighted in &mut weighted.services {
+                scope_reference(&mut weighted.name, &labeled.services, scope);
+            }
+        }
+    }
+}
+
+/// Services that may load balance between servers.
+trait LoadBalanced {
+    type Server;
+
+    /// Servers of the service, `None` unless it is a load balancer.
+    fn servers(&mut self) -> Option<&mut Vec<Self::Server>>;
+}
+
+impl LoadBalanced for ServiceConfig {
+    type Server = ServerConfig;
+
+    fn servers(&mut self) -> Option<&mut Vec<ServerConfig>> {
+        match self {
+            ServiceConfig::LoadBalancer(lb) => Some(&mut lb.servers),
+            _ => None,
+        }
+    }
+}
+
+impl LoadBalanced for TcpServiceConfig {
+    type Server = TcpServerConfig;
+
+    fn servers(&mut self) -> Option<&mut Vec<TcpServerConfig>> {
+        match self {
+            TcpServiceConfig::LoadBalancer(lb) => Some(&mut lb.servers),
+            _ => None,
+        }
+    }
+}
+
+impl LoadBalanced for UdpServiceConfig {
+    type Server = UdpServerConfig;
+
+    fn servers(&mut self) -> Option<&mut Vec<UdpServerConfig>> {
+        match self {
+            UdpServiceConfig::LoadBalancer(lb) => Some(&mut lb.servers),
+            _ => None,
+        }
+    }
+}
+
+/// Routers that forward to a service.
+trait Routed {
+    fn service(&mut self) -> &mut String;
+}
+
+impl Routed for TcpRouterConfig {
+    fn service(&mut self) -> &mut String {
+        &mut self.service
+    }
+}
+
+impl Routed for UdpRouterConfig {
+    fn service(&mut self) -> &mut String {
+        &mut self.service
+    }
+}
+
+impl ContainerInfo {
+    /// Containers sharing the `service` label, or replicas of the same swarm
+    /// service (or compose service with `--compose-replicas`), are load
+    /// balanced behind the same router and service.
+    fn replica_group(&self) -> Option<String> {
+        self.labels
+            .get(&label_key("service"))
+            .cloned()
+            .or_else(|| self.replica_of.clone())
+    }
+
+    /// Weight of this container among its replicas, from the `weight` label.
+    fn weight(&self) -> u32 {
+        match self.labels.get(&label_key("weight")) {
+            Some(weight) => weight.trim().parse().unwrap_or_else(|_| {
+                println!("Invalid '{}' label on container '{:?}': {}", label_key("weight"), self.name, weight);
+                1
+            }),
+            None => 1,
+        }
+    }
+
+    /// `host:port` Traefik should use to reach `port` of this container.
+    fn backend_address(&self, port: &str, protocol: PortTypeEnum) -> String {
+        if self.backend == BackendMode::Host {
+            let published = port.parse::<u16>().ok().and_then(|port| {
+                self.ports.iter().find(|p| {
+                    p.private_port == port
+                        && p.public_port.is_some()
+                        && p.typ.unwrap_or(PortTypeEnum::TCP) == protocol
+                })
+            });
+            match published {
+                Some(published) => {
+                    let host = self.host_address.clone().or_else(|| {
+                        published
+                            .ip
+                            .clone()
+                            .filter(|ip| ip != "0.0.0.0" && ip != "::")
+                    });
+                    match host {
+                        Some(host) => {
+                            return format!("{}:{}", host, published.public_port.unwrap_or_default())
+                        }
+                        None => println!(
+                            "WARNING: no host address known for container '{:?}', please set --host-address",
+                            self.name
+                        ),
+                    }
+                }
+                None => println!(
+                    "WARNING: port {} of container '{:?}' is not published, using the container address",
+                    port, self.name
+                ),
+            }
+        }
+
+        format!("{}:{}", self.ip, port)
+    }
+
+    fn server_url(&self, port: &str) -> String {
+        format!("http://{}", self.backend_address(port, PortTypeEnum::TCP))
+    }
+
+    fn default_server_url(&self) -> String {
+        self.server_url(
+            self.labels
+                .get(&label_key("port"))
+                .map(String::as_str)
+                .unwrap_or("80"),
+        )
+    }
+
+    /// The container's default load balancer, on the `port` label.
+    fn default_load_balancer(&self) -> ServiceConfig {
+        load_balancer(
+            self.default_server_url(),
+            self.weight(),
+            self.decode_section("healthcheck"),
+        )
+    }
+
+    /// Decodes an optional section such as `tls` from the `<namespace>.*`
+    /// labels. A bare `<namespace>=true` label enables the section with its
+    /// defaults and `<namespace>=false` disables it.
+    fn decode_section<T: DeserializeOwned>(&self, namespace: &str) -> Option<T> {
+        let flag = label_key(namespace);
+        let enabled = self
+            .labels
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(&flag))
+            .map(|(_, v)| v.trim().to_ascii_lowercase());
+        if enabled.as_deref() == Some("false") {
+            return None;
+        }
+
+        let tree = match label_tree(&self.labels, namespace) {
+            Ok(tree) => tree,
+            Err(e) => {
+                println!("Invalid '{}' labels on container '{:?}': {}", label_key(namespace), self.name, e);
+                return None;
+            }
+        };
+        if tree.is_empty() && enabled.as_deref() != Some("true") {
+            return None;
+        }
+
+        match tree.decode::<T>() {
+            Ok(section) => Some(section),
+            Err(e) => {
+                println!("Invalid '{}' labels on container '{:?}': {}", label_key(namespace), self.name, e);
+                None
+            }
+        }
+    }
+
+    /// Decodes every entry under `<LABEL_PREFIX><namespace>.<name>` into a `T`,
+    /// skipping (and reporting) the ones that are invalid.
+    fn decode_entries<T: DeserializeOwned>(&self, namespace: &str) -> BTreeMap<String, T> {
+        let tree = match label_tree(&self.labels, namespace) {
+            Ok(tree) => tree,
+            Err(e) => {
+                println!("Invalid '{}' labels on container '{:?}': {}", label_key(namespace), self.name, e);
+                return BTreeMap::new();
+            }
+        };
+
+        let mut entries = BTreeMap::new();
+        for (name, node) in tree.children() {
+            match node.decode::<T>() {
+                Ok(entry) => {
+                    entries.insert(name.clone(), entry);
+                }
+                Err(e) => println!(
+                    "Invalid '{}.{}' on container '{:?}': {}",
+                    label_key(namespace),
+                    name,
+                    self.name,
+                    e
+                ),
+            }
+        }
+        entries
+    }
+
+    /// Whether a service group opts in to the container's `healthcheck.*`
+    /// labels, with a bare `services.<group>.healthCheck=true`. Other groups
+    /// only get the health check of their own labels, as the container's one
+    /// is meant for its default port.
+    fn inherits_health_check(&self, group: &str) -> bool {
+        let flag = label_key(&format!("services.{}.healthcheck", group)).to_ascii_lowercase();
+        let mut inherits = false;
+        for (key, value) in &self.labels {
+            let key = key.to_ascii_lowercase();
+            if key == flag {
+                inherits = value.trim().eq_ignore_ascii_case("true");
+            } else if key.starts_with(&format!("{}.", flag)) {
+                return false;
+            }
+        }
+        inherits
+    }
+
+    /// Builds one router per `routers.<group>` and one service per
+    /// `services.<group>` label group, named after the container and the group.
+    /// Routers without a service use the service group with the same name, the
+    /// only service group, or the container's default service, in that order.
+    fn grouped_http_config(&self, base_name: &str, default_service: &str) -> HttpConfig {
+        let group_name = |group: &str, kind: &str| format!("{}-{}-{}", base_name, group, kind);
+
+        let service_groups = self.decode_entries::<ServiceLabels>("services");
+        let mut services = BTreeMap::new();
+        for (group, service) in &service_groups {
+            let url = match service.port {
+                Some(port) => self.server_url(&port.to_string()),
+                None => self.default_server_url(),
+            };
+            let health_check = if self.inherits_health_check(group) {
+                self.decode_section("healthcheck")
+            } else {
+                service.health_check.clone()
+            };
+            services.insert(
+                group_name(group, "service"),
+                load_balancer(url, self.weight(), health_check),
+            );
+        }
+
+        let mut routers = BTreeMap::new();
+        for (group, router) in self.decode_entries::<RouterLabels>("routers") {
+            let service_group = match router.service {
+                Some(service) => Some(service),
+                None if service_groups.contains_key(&group) => Some(group.clone()),
+                None if service_groups.len() == 1 => service_groups.keys().next().cloned(),
+                None => None,
+            };
+            let service = match service_group {
+                Some(service) if service_groups.contains_key(&service) => group_name(&service, "service"),
+                // Not one of ours, assume it is defined elsewhere.
+                Some(service) => service,
+                None => {
+                    services
+                        .entry(default_service.to_string())
+                        .or_insert_with(|| self.default_load_balancer());
+                    default_service.to_string()
+                }
+            };
+
+            routers.insert(
+                group_name(&group, "router"),
+                RouterConfig {
+                    entry_points: router.entry_points.unwrap_or_else(|| vec!["http".to_string()]),
+                    middlewares: router.middlewares,
+                    service,
+                    rule: router.rule,
+                    rule_syntax: None,
+                    priority: None,
+                    tls: router.tls,
+                    observability: None,
+                },
+            );
+        }
+
+        HttpConfig {
+            routers,
+            services,
+            middlewares: BTreeMap::new(),
+        }
+    }
+
+    /// Builds the routers, services and middlewares defined by the
+    /// `http.*` label namespace. Load balancers without servers point at this
+    /// container and routers without a service use the only labeled service,
+    /// or the container's default service, which is then created.
+    fn labeled_http_config(&self, default_service: &str) -> HttpConfig {
+        let mut services = self.decode_entries::<ServiceConfig>("http.services");
+        for service in services.values_mut() {
+            if let ServiceConfig::LoadBalancer(lb) = service {
+                if lb.servers.is_empty() {
+                    lb.servers.push(ServerConfig {
+                        url: self.default_server_url(),
+                        weight: self.weight(),
+                        preserve_path: true,
+                    });
+                }
+            }
+        }
+
+        let only_service = match services.len() {
+            1 => services.keys().next().cloned(),
+            _ => None,
+        };
+        let mut routers = self.decode_entries::<RouterConfig>("http.routers");
+        for router in routers.values_mut() {
+            if router.service.is_empty() {
+                router.service = match only_service {
+                    Some(ref service) => service.clone(),
+                    None => {
+                        services
+                            .entry(default_service.to_string())
+                            .or_insert_with(|| self.default_load_balancer());
+                        default_service.to_string()
+                    }
+                };
+            }
+        }
+
+        let mut middlewares = self.decode_entries::<MiddlewareConfig>("middlewares");
+        middlewares.extend(self.decode_entries::<MiddlewareConfig>("http.middlewares"));
+
+        HttpConfig {
+            routers,
+            services,
+            middlewares,
+        }
+    }
+
+    /// Points the load balancers without servers at this container on the
+    /// `port` label, and the routers without a service at the container's
+    /// only service. When the container has no service, routers without one
+    /// get `<base_name>-service`, a load balancer on the `port` label. Services
+    /// and routers left incomplete are dropped.
+    fn fill_defaults<S: LoadBalanced, R: Routed>(
+        &self,
+        protocol: PortTypeEnum,
+        base_name: &str,
+        services: &mut BTreeMap<String, S>,
+        routers: &mut BTreeMap<String, R>,
+        server: impl Fn(String) -> S::Server,
+        load_balancer: impl Fn(Vec<S::Server>) -> S,
+    ) {
+        let kind = match protocol {
+            PortTypeEnum::UDP => "UDP",
+            _ => "TCP",
+        };
+        let port = self.labels.get(&label_key("port"));
+        services.retain(|name, service| match service.servers() {
+            Some(servers) if servers.is_empty() => match port {
+                Some(port) => {
+                    servers.push(server(self.backend_address(port, protocol)));
+                    true
+                }
+                None => {
+                    println!("{} service '{}' on container '{:?}' has no servers. Please specify a port with the label '{}'", kind, name, self.name, label_key("port"));
+                    false
+                }
+            },
+            _ => true,
+        });
+
+        if let Some(port) = port {
+            if services.is_empty() && routers.values_mut().any(|router| router.service().is_empty()) {
+                services.insert(
+                    format!("{}-service", base_name),
+                    load_balancer(vec![server(self.backend_address(port, protocol))]),
+                );
+            }
+        }
+
+        let only_service = match services.len() {
+            1 => services.keys().next().cloned(),
+            _ => None,
+        };
+        routers.retain(|name, router| {
+            let service = router.service();
+            if service.is_empty() {
+                match &only_service {
+                    Some(only_service) => *service = only_service.clone(),
+                    None if port.is_none() => {
+                        println!("{} router '{}' on container '{:?}' has no service. Please specify a port with the label '{}'", kind, name, self.name, label_key("port"));
+                        return false;
+                    }
+                    None => {
+                        println!("{} router '{}' on container '{:?}' has no service, and the container has several", kind, name, self.name);
+                        return false;
+                    }
+                }
+            }
+            true
+        });
+    }
+
+    /// Builds the TCP routers, services and middlewares defined by the `tcp.*`
+    /// label namespace, with the defaults of `fill_defaults`.
+    fn labeled_tcp_config(&self, base_name: &str) -> TcpConfig {
+        let mut tcp = TcpConfig {
+            routers: self.decode_entries::<TcpRouterConfig>("tcp.routers"),
+            services: self.decode_entries::<TcpServiceConfig>("tcp.services"),
+            middlewares: self.decode_entries::<TcpMiddlewareConfig>("tcp.middlewares"),
+        };
+        if let Some(ref scope) = self.label_scope {
+            let labeled = LabeledNames::new(&tcp.routers, &tcp.services, &tcp.middlewares);
+            scope_tcp(&mut tcp, &labeled, scope);
+        }
+        self.fill_defaults(
+            PortTypeEnum::TCP,
+            base_name,
+            &mut tcp.services,
+            &mut tcp.routers,
+            |address| TcpServerConfig { address, tls: None },
+            |servers| {
+                TcpServiceConfig::LoadBalancer(TcpLoadBalancerConfig {
+                    servers,
+                    proxy_protocol: None,
+                    servers_transport: None,
+                })
+            },
+        );
+        tcp
+    }
+
+    /// Builds the UDP routers and services defined by the `udp.*` label
+    /// namespace, with the defaults of `fill_defaults`.
+    fn labeled_udp_config(&self, base_name: &str) -> UdpConfig {
+        let mut udp = UdpConfig {
+            routers: self.decode_entries::<UdpRouterConfig>("udp.routers"),
+            services: self.decode_entries::<UdpServiceConfig>("udp.services"),
+        };
+        if let Some(ref scope) = self.label_scope {
+            let labeled = LabeledNames::new(&udp.routers, &udp.services, &BTreeMap::<String, ()>::new());
+            scope_udp(&mut udp, &labeled, scope);
+        }
+        self.fill_defaults(
+            PortTypeEnum::UDP,
+            base_name,
+            &mut udp.services,
+            &mut udp.routers,
+            |address| UdpServerConfig { address },
+            |servers| UdpServiceConfig::LoadBalancer(UdpLoadBalancerConfig { servers }),
+        );
+        udp
+    }
+
+    /// Builds every HTTP router, service and middleware of the container, with
+    /// generated names derived from `base_name` and labeled ones scoped to the
+    /// container's endpoint.
+    fn http_config(&self, base_name: &str, has_l4_routers: bool) -> HttpConfig {
+        let service_name = format!("{}-service", base_name);
+        let router_name = format!("{}-router", base_name);
+
+        let mut container_http = self.labeled_http_config(&service_name);
+        let mut labeled = LabeledNames::new(
+            &container_http.routers,
+            &container_http.services,
+            &container_http.middlewares,
+        );
+        // Generated from the container name, which is qualified already.
+        labeled.services.remove(&service_name);
+        container_http.merge(self.grouped_http_config(base_name, &service_name));
+
+        let router_rule = self.labels.get(&label_key("rule")).cloned().unwrap_or_default();
+        if router_rule.is_empty() {
+            if container_http.routers.is_empty() && !has_l4_routers {
+                println!("Rule is empty for container '{:?}'. Please specify a rule with the label '{}'", self.name, label_key("rule"));
+            }
+        } else {
+            let router = RouterConfig {
+                entry_points: self
+                    .labels
+                    .get(&label_key("entrypoints"))
+                    .map(|s| s.split(',').map(String::from).collect())
+                    .unwrap_or_else(|| vec!["http".to_string()]),
+                middlewares: self
+                    .labels
+                    .get(&label_key("middlewares"))
+                    .map(|s| s.split(',').map(String::from).collect())
+                    .unwrap_or_default(),
+                service: service_name.clone(),
+                rule: router_rule,
+                rule_syntax: None,
+                priority: None,
+                tls: self.decode_section("tls"),
+                observability: None,
+            };
+            container_http
+                .services
+                .entry(service_name)
+                .or_insert_with(|| self.default_load_balancer());
+            container_http.routers.entry(router_name).or_insert(router);
+        }
+
+        if let Some(ref scope) = self.label_scope {
+            scope_http(&mut container_http, &labeled, scope);
+        }
+        container_http
+    }
 }
 
 impl From<Vec<ContainerInfo>> for TraefikConfig {
     fn from(container_infos: Vec<ContainerInfo>) -> Self {
-        let mut routers = std::collections::HashMap::new();
-        let mut services = std::collections::HashMap::new();
-        let middlewares = std::collections::HashMap::new();
+        let mut http = HttpConfig::default();
+        let mut tcp = TcpConfig::default();
+        let mut udp = UdpConfig::default();
 
         for container in container_infos {
             if let Some(enabled) = container.labels.get(&label_key("enabled")) {
                 if enabled == "true" {
-                    let service_name = format!("{}-service", container.name);
-                    let router_name = format!("{}-router", container.name);
-
-                    let service = ServiceConfig::LoadBalancer(LoadBalancerConfig {
-                        sticky: None,
-                        servers: vec![ServerConfig {
-                            url: format!(
-                                "http://{}:{}",
-                                container.ip,
-                                container
-                                    .labels
-                                    .get(&label_key("port"))
-                                    .unwrap_or(&"80".to_string())
-                            ),
-                            weight: 1,
-                            preserve_path: true,
-                        }],
-                        health_check: None,
-                        pass_host_header: None,
-                        response_forwarding: None,
-                        servers_transport: None,
-                    });
+                    let replica_group = container.replica_group();
+
+                    let base_name = replica_group.as_deref().unwrap_or(&container.name);
+
+                    let mut container_tcp = container.labeled_tcp_config(base_name);
+                    for (name, router) in &container_tcp.routers {
+                        println!(
+                            " - Exposing {} from {} as TCP {}: {}",
+                            container.name, container.ip, name, router.rule
+                        );
+                    }
+                    let has_tcp_routers = !container_tcp.routers.is_empty();
+                    if replica_group.is_some() {
+                        merge_replica_services(&mut tcp.services, &mut container_tcp.services);
+                        dedup_replica_entries(&tcp.routers, &mut container_tcp.routers);
+                        container_tcp.middlewares.retain(|name, _| !tcp.middlewares.contains_key(name));
+                    }
+                    tcp.merge(container_tcp);
 
-                    let router_rule = container
-                        .labels
-                        .get(&label_key("rule"))
-                        .unwrap_or(&"".to_string())
-                        .clone();
-
-                    if router_rule.is_empty() {
-                        println!("Rule is empty for container '{:?}'. Please specify a rule with the label '{}'", container.name, label_key("rule"));
-                        continue;
-                    }
-
-                    println!(
-                        " - Exposing {} from {}: {}",
-                        container.name, container.ip, router_rule
-                    );
-
-                    let router = RouterConfig {
-                        entry_points: container
-                            .labels
-                            .get(&label_key("entrypoints"))
-                            .map(|s| s.split(',').map(String::from).collect())
-                            .unwrap_or_else(|| vec!["http".to_string()]),
-                        middlewares: container
-                            .labels
-                            .get(&label_key("middlewares"))
-                            .map(|s| s.split(',').map(String::from).collect())
-                            .unwrap_or_default(),
-                        service: service_name.clone(),
-                        rule: router_rule,
-                        rule_syntax: None,
-                        priority: None,
-                        tls: None,
-                        observability: None,
-                    };
-
-                    services.insert(service_name, service);
-                    routers.insert(router_name, router);
+                    let mut container_udp = container.labeled_udp_config(base_name);
+                    for (name, router) in &container_udp.routers {
+                        println!(
+                            " - Exposing {} from {} as UDP {} on {}",
+                            container.name,
+                            container.ip,
+                            name,
+                            router.entry_points.join(",")
+                        );
+                    }
+                    let has_udp_routers = !container_udp.routers.is_empty();
+                    if replica_group.is_some() {
+                        merge_replica_services(&mut udp.services, &mut container_udp.services);
+                        dedup_replica_entries(&udp.routers, &mut container_udp.routers);
+                    }
+                    udp.merge(container_udp);
+
+                    let has_l4_routers = has_tcp_routers || has_udp_routers;
+                    let mut container_http = container.http_config(base_name, has_l4_routers);
+                    let mut replica = replica_group.is_some();
+                    if let Some(ref group) = replica_group {
+                        // Replicas share their routers, one that differs would
+                        // be dropped, or send its traffic to the other replicas.
+                        if let Some(router) = conflicting_router(&http.routers, &container_http.routers) {
+                            println!(
+                                "WARNING: router '{}' of container '{:?}' differs from the one of the other replicas of '{}', exposing the container on its own",
+                                router, container.name, group
+                            );
+                            container_http = container.http_config(&container.name, has_l4_routers);
+                            replica = false;
+                        }
+                    }
+                    for (name, router) in &container_http.routers {
+                        println!(
+                            " - Exposing {} from {} as {}: {}",
+                            container.name, container.ip, name, router.rule
+                        );
+                    }
+                    if replica {
+                        merge_replica_services(&mut http.services, &mut container_http.services);
+                        dedup_replica_entries(&http.routers, &mut container_http.routers);
+                        container_http.middlewares.retain(|name, _| !http.middlewares.contains_key(name));
+                    }
+                    http.merge(container_http);
                 }
             }
         }
         println!(
-            "Created {} routers and {} services",
-            routers.len(),
-            services.len()
+            "Created {} routers, {} services and {} middlewares",
+            http.routers.len(),
+            http.services.len(),
+            http.middlewares.len()
         );
+        if !tcp.is_empty() {
+            println!(
+                "Created {} TCP routers and {} TCP services",
+                tcp.routers.len(),
+                tcp.services.len()
+            );
+        }
+        if !udp.is_empty() {
+            println!(
+                "Created {} UDP routers and {} UDP services",
+                udp.routers.len(),
+                udp.services.len()
+            );
+        }
 
         TraefikConfig {
-            http: Some(HttpConfig {
-                routers,
-                services,
-                middlewares,
-            }),
+            http: Some(http),
+            tcp: (!tcp.is_empty()).then_some(tcp),
+            udp: (!udp.is_empty()).then_some(udp),
+        }
+    }
+}
+
+/// Picks the container's IP on `requested` network, or on the first attached
+/// network (by name) when none was requested or it isn't attached.
+fn container_ip(
+    name: &str,
+    networks: &HashMap<String, EndpointSettings>,
+    requested: Option<&String>,
+) -> String {
+    if let Some(requested) = requested {
+        match networks.get(requested) {
+            Some(endpoint) => return endpoint.ip_address.clone().unwrap_or_default(),
+            None => {
+                let mut attached = networks.keys().cloned().collect::<Vec<_>>();
+                attached.sort();
+                println!(
+                    "WARNING: container '{:?}' is not attached to network '{}' (attached to: {}), falling back to another network",
+                    name,
+                    requested,
+                    attached.join(", ")
+                );
+            }
         }
     }
+
+    let mut networks = networks.iter().collect::<Vec<_>>();
+    networks.sort_by_key(|(network, _)| network.as_str());
+    networks
+        .first()
+        .and_then(|(_, endpoint)| endpoint.ip_address.clone())
+        .unwrap_or_default()
+}
+
+/// Backend mode from the `backend` label, or `default` when there is none.
+fn labeled_backend(name: &str, labels: &HashMap<String, String>, default: BackendMode) -> BackendMode {
+    match labels.get(&label_key("backend")) {
+        Some(mode) => BackendMode::from_str(mode, true).unwrap_or_else(|e| {
+            println!("Invalid '{}' label on container '{:?}': {}", label_key("backend"), name, e);
+            default
+        }),
+        None => default,
+    }
 }
 
 impl DockerProvider {
-    pub fn new() -> anyhow::Result<Self> {
-        let client = bollard::Docker::connect_with_local_defaults()?;
+    pub async fn new(options: DockerOptions) -> anyhow::Result<Self> {
+        let mut endpoints = Vec::new();
+        for spec in &options.endpoints {
+            endpoints.push(DockerEndpoint::connect(spec).await?);
+        }
+        if endpoints.is_empty() {
+            endpoints.push(DockerEndpoint::local().await?);
+        }
+
         Ok(DockerProvider {
-            client,
+            endpoints,
+            options,
             memory: None,
             dirty: AtomicBool::new(false),
+            unreachable: Vec::new(),
         })
     }
 
-    pub fn mark_dirty(&self) {
-        self.dirty.store(true, std::sync::atomic::Ordering::Relaxed);
-    }
-
     pub async fn get_exposable_containers_info(&mut self) -> anyhow::Result<TraefikConfig> {
-        if self.dirty.load(std::sync::atomic::Ordering::Relaxed) {
+        // Keep trying unreachable endpoints instead of serving the cache.
+        if self.dirty.load(std::sync::atomic::Ordering::Relaxed) || !self.unreachable.is_empty() {
             self.memory = None;
         }
         if let Some(ref memory) = self.memory {
             return Ok(memory.clone());
         }
 
+        let mut container_info_list = Vec::new();
+
+        println!("updating memory...");
+        // Only qualify names when aggregating hosts, to keep them stable for
+        // single host setups.
+        let qualify = self.endpoints.len() > 1;
+        self.unreachable.clear();
+        let mut listed = false;
+        for idx in 0..self.endpoints.len() {
+            let listing = match self.endpoints[idx].reconnect().await {
+                Ok(()) => match self.options.swarm {
+                    true => swarm::list_services(&self.endpoints[idx], &self.options, qualify).await,
+                    false => self.list_endpoint_containers(&self.endpoints[idx], qualify).await,
+                },
+                Err(e) => Err(e),
+            };
+            let endpoint = &mut self.endpoints[idx];
+            match listing {
+                Ok(containers) => {
+                    endpoint.last_containers = Some(containers.clone());
+                    container_info_list.extend(containers);
+                    listed = true;
+                }
+                Err(e) => {
+                    // Hosts never listed are left out rather than holding back
+                    // the others.
+                    match endpoint.last_containers {
+                        Some(ref containers) => {
+                            println!(
+                                "WARNING: unable to list containers of docker endpoint '{}', using its last known containers: {:#}",
+                                endpoint.name, e
+                            );
+                            container_info_list.extend(containers.iter().cloned());
+                            listed = true;
+                        }
+                        None => println!(
+                            "WARNING: unable to list containers of docker endpoint '{}', skipping it: {:#}",
+                            endpoint.name, e
+                        ),
+                    }
+                    self.unreachable.push(format!("docker endpoint '{}': {:#}", endpoint.name, e));
+                }
+            }
+        }
+        if !listed {
+            anyhow::bail!("no docker endpoint could be listed: {}", self.unreachable.join(", "));
+        }
+
+        let traefik_config: TraefikConfig = container_info_list.into();
+
+        self.memory = Some(traefik_config.clone());
+        self.dirty
+            .store(false, std::sync::atomic::Ordering::Relaxed);
+
+        Ok(traefik_config)
+    }
+
+    async fn list_endpoint_containers(
+        &self,
+        endpoint: &DockerEndpoint,
+        qualify: bool,
+    ) -> anyhow::Result<Vec<ContainerInfo>> {
         let options = Some(ListContainersOptions::<String> {
             all: false,
             ..Default::default()
         });
 
-        let containers = self.client.list_containers(options).await?;
+        let containers = endpoint.client()?.list_containers(options).await?;
+
+        // Containers sharing the network namespace of another one, such as
+        // the members of a Podman pod, have the networks and published ports
+        // of that container (the pod's infra container).
+        let mut namespaces = HashMap::new();
+        for container in &containers {
+            let networks = container
+                .network_settings
+                .as_ref()
+                .and_then(|settings| settings.networks.clone())
+                .unwrap_or_default();
+            let ports = container.ports.clone().unwrap_or_default();
+            let keys = container.id.iter().cloned().chain(
+                container
+                    .names
+                    .iter()
+                    .flatten()
+                    .map(|name| name.trim_start_matches('/').to_string()),
+            );
+            for key in keys {
+                namespaces.insert(key, (networks.clone(), ports.clone()));
+            }
+        }
 
         let mut container_info_list = Vec::new();
 
-        println!("updating memory...");
         for container in containers {
-            let labels: HashMap<String, String> = container
-                .labels
-                .unwrap_or_default()
+            let all_labels = container.labels.unwrap_or_default();
+            let replica_of = match (
+                self.options.compose_replicas,
+                all_labels.get(COMPOSE_PROJECT_LABEL),
+                all_labels.get(COMPOSE_SERVICE_LABEL),
+            ) {
+                (true, Some(project), Some(service)) => Some(match qualify {
+                    true => format!("{}/{}-{}", endpoint.name, project, service),
+                    false => format!("{}-{}", project, service),
+                }),
+                _ => None,
+            };
+            let labels: HashMap<String, String> = all_labels
                 .into_iter()
                 .filter(|(k, _v)| k.starts_with(LABEL_PREFIX))
                 .collect();
@@ -163,55 +1240,475 @@ impl DockerProvider {
                 .first()
                 .cloned()
                 .unwrap_or_default();
-            let network_settings = container.network_settings.unwrap_or_default();
-            let networks = network_settings.networks.unwrap_or_default();
-            let ip = networks
-                .values()
-                .next()
-                .map(|n| n.ip_address.clone())
-                .unwrap_or_default()
-                .unwrap_or_default();
+            let name = match qualify {
+                true => format!("{}{}", endpoint.name, name),
+                false => name,
+            };
 
-            container_info_list.push(ContainerInfo { name, ip, labels });
-        }
+            let health = HealthStatus::from_status(container.status.as_deref().unwrap_or_default());
+            if !self.options.health_policy.allows(health) {
+                println!("Skipping container '{:?}', its health is {:?}", name, health);
+                continue;
+            }
 
-        let traefik_config: TraefikConfig = container_info_list.into();
+            let network_settings = container.network_settings.unwrap_or_default();
+            let mut networks = network_settings.networks.unwrap_or_default();
+            let mut ports = container.ports.unwrap_or_default();
+            let shared_namespace = container
+                .host_config
+                .and_then(|config| config.network_mode)
+                .and_then(|mode| mode.strip_prefix("container:").map(String::from))
+                .and_then(|owner| namespaces.get(&owner));
+            if let Some((owner_networks, owner_ports)) = shared_namespace {
+                if networks.is_empty() {
+                    networks = owner_networks.clone();
+                }
+                if ports.is_empty() {
+                    ports = owner_ports.clone();
+                }
+            }
+            let requested_network = labels
+                .get(&label_key("network"))
+                .or(self.options.network.as_ref());
+            let ip = container_ip(&name, &networks, requested_network);
 
-        self.memory = Some(traefik_config.clone());
-        self.dirty
-            .store(false, std::sync::atomic::Ordering::Relaxed);
+            let backend = labeled_backend(&name, &labels, endpoint.backend.unwrap_or(self.options.backend));
 
-        Ok(traefik_config)
+            container_info_list.push(ContainerInfo {
+                name,
+                ip,
+                labels,
+                ports,
+                backend,
+                host_address: endpoint
+                    .address
+                    .clone()
+                    .or_else(|| self.options.host_address.clone()),
+                replica_of,
+                label_scope: qualify.then(|| endpoint.name.clone()),
+            });
+        }
+
+        Ok(container_info_list)
     }
 
-    pub fn watch_container_events(&self, actions: Vec<String>) -> Receiver<EventMessage> {
+    /// Streams the events of every endpoint whose action is one of `actions`.
+    /// Events are filtered by the daemon, on container and network events, or
+    /// on service events in swarm mode.
+    ///
+    /// Streams that drop are reconnected with an exponential backoff, replaying
+    /// the events since the last one seen. Events the daemon no longer has
+    /// (e.g. after a restart) are covered by a `DockerEvent::Reconnected`,
+    /// sent once the new stream delivered an event or stayed open for
+    /// `MIN_STREAM_UPTIME`, so streams failing right away keep backing off.
+    /// A `DockerEvent::Disconnected` is sent as soon as a stream drops.
+    pub fn watch_container_events(&self, actions: Vec<String>) -> Receiver<DockerEvent> {
         let (tx, rx) = tokio::sync::mpsc::channel(100);
 
-        let client = self.client.clone();
-        task::spawn(async move {
-            let mut events_stream = client.events::<String>(Some(EventsOptions {
-                since: Some(chrono::Utc::now()),
-                until: None,
-                filters: Default::default(),
-            }));
-
-            while let Some(event_result) = events_stream.next().await {
-                match event_result {
-                    Ok(event) => {
-                        if let Some(ref action) = event.action {
-                            if actions.contains(action) && tx.send(event).await.is_err() {
-                                eprintln!("Receiver dropped");
-                                return;
+        let types = match self.options.swarm {
+            true => vec!["service".to_string()],
+            false => vec!["container".to_string(), "network".to_string()],
+        };
+        // Also ask for the Podman names, they are translated back once received.
+        let mut events = actions.clone();
+        events.extend(actions.iter().filter_map(|a| podman_action(a)).map(String::from));
+        let filters = HashMap::from([("type".to_string(), types), ("event".to_string(), events)]);
+
+        for endpoint in &self.endpoints {
+            let name = endpoint.name.clone();
+            let mut clients = endpoint.subscribe();
+            let actions = actions.clone();
+            let filters = filters.clone();
+            let tx = tx.clone();
+            task::spawn(async move {
+                // Endpoints that were down at startup connect on a later listing.
+                let client = loop {
+                    let client = clients.borrow_and_update().clone();
+                    match client {
+                        Some(client) => break client,
+                        None if clients.changed().await.is_err() => return,
+                        None => {}
+                    }
+                };
+                let mut since = chrono::Utc::now();
+                let mut backoff = MIN_RECONNECT_BACKOFF;
+                let mut connected = true;
+
+                loop {
+                    let mut events_stream = client.events(Some(EventsOptions {
+                        since: Some(since),
+                        until: None,
+                        filters: filters.clone(),
+                    }));
+                    let opened = Instant::now();
+                    let mut healthy = false;
+
+                    loop {
+                        // `None` while an untrusted stream is still open.
+                        let next = match healthy {
+                            true => Some(events_stream.next().await),
+                            false => timeout_at(opened + MIN_STREAM_UPTIME, events_stream.next()).await.ok(),
+                        };
+                        if !healthy && matches!(next, None | Some(Some(Ok(_)))) {
+                            healthy = true;
+                            backoff = MIN_RECONNECT_BACKOFF;
+                            if !connected {
+                                connected = true;
+                                println!("Reconnected to the event stream of docker endpoint '{}'", name);
+                                if tx.send(DockerEvent::Reconnected).await.is_err() {
+                                    return;
+                                }
+                            }
+                        }
+
+                        match next {
+                            None => {}
+                            Some(None) => break,
+                            Some(Some(Ok(event))) => {
+                                // Whole seconds: replaying the last second again
+                                // is harmless, missing part of it is not.
+                                if let Some(time) = event.time.and_then(|t| chrono::DateTime::from_timestamp(t, 0)) {
+                                    since = time;
+                                }
+                                let watched = event_action(&event).is_some_and(|a| actions.iter().any(|w| w == a));
+                                if watched && tx.send(DockerEvent::Event(event)).await.is_err() {
+                                    eprintln!("Receiver dropped");
+                                    return;
+                                }
+                            }
+                            Some(Some(Err(e))) => {
+                                eprintln!("Error receiving events from docker endpoint '{}': {:?}", name, e);
+                                break;
                             }
                         }
                     }
-                    Err(e) => {
-                        eprintln!("Error receiving event: {:?}", e);
+
+                    if connected {
+                        connected = false;
+                        if tx.send(DockerEvent::Disconnected).await.is_err() {
+                            return;
+                        }
+                    }
+
+                    // Wait for the daemon to answer again before subscribing.
+                    loop {
+                        eprintln!(
+                            "Lost the event stream of docker endpoint '{}', reconnecting in {:?}",
+                            name, backoff
+                        );
+                        tokio::time::sleep(backoff).await;
+                        backoff = (backoff * 2).min(MAX_RECONNECT_BACKOFF);
+                        if tx.is_closed() {
+                            return;
+                        }
+                        match client.ping().await {
+                            Ok(_) => break,
+                            Err(e) => eprintln!("Unable to reach docker endpoint '{}': {:?}", name, e),
+                        }
                     }
                 }
+            });
+        }
+
+        rx
+    }
+}
+
+#[async_trait]
+impl Provider for DockerProvider {
+    fn name(&self) -> &str {
+        "docker"
+    }
+
+    async fn get_config(&mut self) -> anyhow::Result<TraefikConfig> {
+        self.get_exposable_containers_info().await
+    }
+
+    fn stale_reason(&self) -> Option<String> {
+        match self.unreachable.is_empty() {
+            true => None,
+            false => Some(self.unreachable.join(", ")),
+        }
+    }
+
+    fn mark_dirty(&self) {
+        self.dirty.store(true, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    fn watch_changes(&self) -> Receiver<()> {
+        let (tx, rx) = tokio::sync::mpsc::channel(100);
+
+        let actions = match (self.options.watch_events.is_empty(), self.options.swarm) {
+            (false, _) => self.options.watch_events.clone(),
+            (true, false) => CONTAINER_EVENTS.iter().map(|a| a.to_string()).collect(),
+            (true, true) => SERVICE_EVENTS.iter().map(|a| a.to_string()).collect(),
+        };
+        let mut events = self.watch_container_events(actions);
+
+        if self.options.swarm && self.options.swarm_resync_secs > 0 {
+            let tx = tx.clone();
+            let mut resync = tokio::time::interval(Duration::from_secs(self.options.swarm_resync_secs));
+            task::spawn(async move {
+                // The first tick is immediate, the initial build covers it.
+                resync.tick().await;
+                loop {
+                    resync.tick().await;
+                    if tx.send(()).await.is_err() {
+                        return;
+                    }
+                }
+            });
+        }
+
+        task::spawn(async move {
+            // Disconnections and reconnections trigger a full resync, like any
+            // other change.
+            while let Some(event) = events.recv().await {
+                if let DockerEvent::Event(ref event) = event {
+                    let actor = event
+                        .actor
+                        .as_ref()
+                        .and_then(|actor| actor.attributes.as_ref()?.get("name").cloned())
+                        .unwrap_or_default();
+                    println!("Received '{}' event for '{}'", event_action(event).unwrap_or_default(), actor);
+                }
+                if tx.send(()).await.is_err() {
+                    return;
+                }
             }
         });
 
         rx
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn container(name: &str, ip: &str, labels: &[(&str, &str)]) -> ContainerInfo {
+        ContainerInfo {
+            name: name.to_string(),
+            ip: ip.to_string(),
+            labels: labels.iter().map(|(k, v)| (label_key(k), v.to_string())).collect(),
+            ports: Vec::new(),
+            backend: BackendMode::Container,
+            host_address: None,
+            replica_of: None,
+            label_scope: None,
+        }
+    }
+
+    /// URLs and weights of the servers of an HTTP load balancer.
+    fn servers(service: &ServiceConfig) -> Vec<(&str, u32)> {
+        match service {
+            ServiceConfig::LoadBalancer(lb) => lb.servers.iter().map(|s| (s.url.as_str(), s.weight)).collect(),
+            _ => panic!("not a load balancer"),
+        }
+    }
+
+    #[test]
+    fn labeled_router_uses_default_service() {
+        let config = TraefikConfig::from(vec![container(
+            "/a",
+            "10.0.0.2",
+            &[("enabled", "true"), ("port", "8080"), ("http.routers.web.rule", "Host(`a`)")],
+        )]);
+        let http = config.http.unwrap();
+        assert_eq!(http.routers["web"].service, "/a-service");
+        assert_eq!(servers(&http.services["/a-service"]), [("http://10.0.0.2:8080", 1)]);
+    }
+
+    #[test]
+    fn tcp_router_gets_implicit_service() {
+        let config = TraefikConfig::from(vec![container(
+            "/pg",
+            "10.0.0.3",
+            &[("enabled", "true"), ("port", "5432"), ("tcp.routers.pg.rule", "HostSNI(`*`)")],
+        )]);
+        let tcp = config.tcp.unwrap();
+        assert_eq!(tcp.routers["pg"].service, "/pg-service");
+        match &tcp.services["/pg-service"] {
+            TcpServiceConfig::LoadBalancer(lb) => assert_eq!(lb.servers[0].address, "10.0.0.3:5432"),
+            _ => panic!("not a load balancer"),
+        }
+        // No HTTP router without a rule.
+        assert!(config.http.unwrap().routers.is_empty());
+    }
+
+    #[test]
+    fn tcp_router_without_port_is_dropped() {
+        let config = TraefikConfig::from(vec![container(
+            "/pg",
+            "10.0.0.3",
+            &[("enabled", "true"), ("tcp.routers.pg.rule", "HostSNI(`*`)")],
+        )]);
+        assert!(config.tcp.is_none());
+    }
+
+    #[test]
+    fn udp_router_gets_implicit_service() {
+        let config = TraefikConfig::from(vec![container(
+            "/dns",
+            "10.0.0.4",
+            &[("enabled", "true"), ("port", "53"), ("udp.routers.dns.entryPoints", "dns")],
+        )]);
+        let udp = config.udp.unwrap();
+        assert_eq!(udp.routers["dns"].service, "/dns-service");
+        assert_eq!(udp.routers["dns"].entry_points, ["dns"]);
+        match &udp.services["/dns-service"] {
+            UdpServiceConfig::LoadBalancer(lb) => assert_eq!(lb.servers[0].address, "10.0.0.4:53"),
+            _ => panic!("not a load balancer"),
+        }
+    }
+
+    #[test]
+    fn namespaces_ignore_case() {
+        let config = TraefikConfig::from(vec![container(
+            "/a",
+            "10.0.0.2",
+            &[("enabled", "true"), ("rule", "Host(`a`)"), ("healthCheck.path", "/x"), ("TLS", "true")],
+        )]);
+        let http = config.http.unwrap();
+        match &http.services["/a-service"] {
+            ServiceConfig::LoadBalancer(lb) => {
+                assert_eq!(lb.health_check.as_ref().unwrap().path.as_deref(), Some("/x"))
+            }
+            _ => panic!("not a load balancer"),
+        }
+        assert!(http.routers["/a-router"].tls.is_some());
+    }
+
+    #[test]
+    fn health_from_status() {
+        assert_eq!(HealthStatus::from_status("Up 5 minutes (healthy)"), HealthStatus::Healthy);
+        assert_eq!(HealthStatus::from_status("Up 3 hours (unhealthy)"), HealthStatus::Unhealthy);
+        assert_eq!(HealthStatus::from_status("Up 2 seconds (health: starting)"), HealthStatus::Starting);
+        assert_eq!(HealthStatus::from_status("Up 10 minutes"), HealthStatus::None);
+        assert_eq!(HealthStatus::from_status("Up About a minute (Paused)"), HealthStatus::None);
+        assert_eq!(HealthStatus::from_status(""), HealthStatus::None);
+    }
+
+    #[test]
+    fn health_policies() {
+        use HealthStatus::{Healthy, Starting, Unhealthy};
+        let none = HealthStatus::None;
+
+        let allowed = |policy: HealthPolicy| {
+            [none, Starting, Healthy, Unhealthy]
+                .into_iter()
+                .filter(|health| policy.allows(*health))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(allowed(HealthPolicy::Ignore), [none, Starting, Healthy, Unhealthy]);
+        assert_eq!(allowed(HealthPolicy::RequireHealthy), [none, Healthy]);
+        assert_eq!(allowed(HealthPolicy::DropUnhealthy), [none, Starting, Healthy]);
+    }
+
+    #[test]
+    fn labeled_names_are_scoped_to_their_host() {
+        let labels = [
+            ("enabled", "true"),
+            ("port", "8080"),
+            ("http.routers.web.rule", "Host(`a`)"),
+            ("http.routers.web.middlewares", "auth,compress@file"),
+            ("http.services.app.loadBalancer", "true"),
+            ("http.middlewares.auth.basicAuth.users", "u:p"),
+            ("tcp.routers.pg.rule", "HostSNI(`*`)"),
+            ("tcp.routers.pg.service", "pg"),
+            ("tcp.services.pg.loadBalancer", "true"),
+        ];
+        let config = TraefikConfig::from(vec![
+            ContainerInfo {
+                label_scope: Some("h1".to_string()),
+                ..container("h1/a", "10.0.0.2", &labels)
+            },
+            ContainerInfo {
+                label_scope: Some("h2".to_string()),
+                ..container("h2/a", "10.0.1.2", &labels)
+            },
+        ]);
+
+        let http = config.http.unwrap();
+        assert_eq!(http.routers.keys().collect::<Vec<_>>(), ["h1/web", "h2/web"]);
+        assert_eq!(http.routers["h1/web"].service, "h1/app");
+        assert_eq!(http.routers["h2/web"].middlewares, ["h2/auth", "compress@file"]);
+        assert_eq!(servers(&http.services["h1/app"]), [("http://10.0.0.2:8080", 1)]);
+        assert_eq!(servers(&http.services["h2/app"]), [("http://10.0.1.2:8080", 1)]);
+        assert_eq!(http.middlewares.keys().collect::<Vec<_>>(), ["h1/auth", "h2/auth"]);
+
+        let tcp = config.tcp.unwrap();
+        assert_eq!(tcp.routers["h2/pg"].service, "h2/pg");
+        assert_eq!(tcp.services.len(), 2);
+    }
+
+    fn replica(name: &str, ip: &str, labels: &[(&str, &str)]) -> ContainerInfo {
+        ContainerInfo {
+            replica_of: Some("app-web".to_string()),
+            ..container(name, ip, labels)
+        }
+    }
+
+    #[test]
+    fn replicas_share_a_load_balancer() {
+        let labels = [("enabled", "true"), ("rule", "Host(`a`)"), ("port", "80")];
+        let config = TraefikConfig::from(vec![
+            replica("/app-web-1", "10.0.0.2", &[&labels[..], &[("weight", "3")]].concat()),
+            replica("/app-web-2", "10.0.0.3", &labels),
+        ]);
+        let http = config.http.unwrap();
+        assert_eq!(http.routers.keys().collect::<Vec<_>>(), ["app-web-router"]);
+        assert_eq!(http.routers["app-web-router"].service, "app-web-service");
+        assert_eq!(http.services.len(), 1);
+        assert_eq!(
+            servers(&http.services["app-web-service"]),
+            [("http://10.0.0.2:80", 3), ("http://10.0.0.3:80", 1)]
+        );
+    }
+
+    #[test]
+    fn differing_replica_routers_are_kept_apart() {
+        let config = TraefikConfig::from(vec![
+            replica("/app-web-1", "10.0.0.2", &[("enabled", "true"), ("rule", "Host(`a`)")]),
+            replica("/app-web-2", "10.0.0.3", &[("enabled", "true"), ("rule", "Host(`b`)")]),
+        ]);
+        let http = config.http.unwrap();
+        assert_eq!(http.routers["app-web-router"].rule, "Host(`a`)");
+        assert_eq!(servers(&http.services["app-web-service"]), [("http://10.0.0.2:80", 1)]);
+        assert_eq!(http.routers["/app-web-2-router"].rule, "Host(`b`)");
+        assert_eq!(http.routers["/app-web-2-router"].service, "/app-web-2-service");
+        assert_eq!(servers(&http.services["/app-web-2-service"]), [("http://10.0.0.3:80", 1)]);
+    }
+
+    #[test]
+    fn tcp_replica_servers_are_appended() {
+        let labels = [("enabled", "true"), ("port", "5432"), ("tcp.routers.pg.rule", "HostSNI(`*`)")];
+        let config = TraefikConfig::from(vec![
+            replica("/app-web-1", "10.0.0.2", &labels),
+            replica("/app-web-2", "10.0.0.3", &labels),
+        ]);
+        let tcp = config.tcp.unwrap();
+        assert_eq!(tcp.routers.len(), 1);
+        assert_eq!(tcp.routers["pg"].service, "app-web-service");
+        match &tcp.services["app-web-service"] {
+            TcpServiceConfig::LoadBalancer(lb) => assert_eq!(
+                lb.servers.iter().map(|s| s.address.as_str()).collect::<Vec<_>>(),
+                ["10.0.0.2:5432", "10.0.0.3:5432"]
+            ),
+            _ => panic!("not a load balancer"),
+        }
+    }
+
+    #[test]
+    fn group_router_falls_back_to_default_service() {
+        let config = TraefikConfig::from(vec![container(
+            "/a",
+            "10.0.0.2",
+            &[("enabled", "true"), ("port", "9000"), ("routers.admin.rule", "Host(`admin`)")],
+        )]);
+        let http = config.http.unwrap();
+        assert_eq!(http.routers.keys().collect::<Vec<_>>(), ["/a-admin-router"]);
+        assert_eq!(http.routers["/a-admin-router"].service, "/a-service");
+        assert_eq!(servers(&http.services["/a-service"]), [("http://10.0.0.2:9000", 1)]);
+    }
+}
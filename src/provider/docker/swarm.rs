@@ -0,0 +1,296 @@
+use bollard::network::InspectNetworkOptions;
+use bollard::secret::{EndpointPortConfig, EndpointPortConfigProtocolEnum, EndpointSettings, Port, PortTypeEnum};
+use bollard::service::ListServicesOptions;
+use clap::ValueEnum;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+use super::endpoint::DockerEndpoint;
+use super::{container_ip, label_key, labeled_backend, ContainerInfo, DockerOptions, LABEL_PREFIX};
+
+/// How the backends of swarm services are addressed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum SwarmBackend {
+    /// The service's virtual IP, load balanced by swarm itself.
+    #[default]
+    Vip,
+    /// Every running task of the service, load balanced by Traefik, on its
+    /// address in the service's overlay network. Tasks that are restarted or
+    /// rescheduled emit no service event, so their new addresses are only
+    /// picked up by the periodic resync of `--swarm-resync-secs`.
+    Tasks,
+}
+
+/// The parts of a network needed to pick backend addresses.
+struct NetworkInfo {
+    name: String,
+    ingress: bool,
+}
+
+/// Networks looked up while listing services, by ID or name.
+#[derive(Default)]
+struct Networks {
+    known: HashMap<String, Option<NetworkInfo>>,
+}
+
+impl Networks {
+    async fn get(&mut self, endpoint: &DockerEndpoint, network: &str) -> Option<&NetworkInfo> {
+        if !self.known.contains_key(network) {
+            let info = match endpoint
+                .client()
+                .ok()?
+                .inspect_network(
+                    network,
+                    Some(InspectNetworkOptions {
+                        verbose: false,
+                        scope: "swarm",
+                    }),
+                )
+                .await
+            {
+                Ok(network) => Some(NetworkInfo {
+                    name: network.name.unwrap_or_default(),
+                    ingress: network.ingress.unwrap_or_default(),
+                }),
+                Err(e) => {
+                    println!("WARNING: unable to inspect network '{}': {}", network, e);
+                    None
+                }
+            };
+            self.known.insert(network.to_string(), info);
+        }
+        self.known.get(network).and_then(Option::as_ref)
+    }
+}
+
+/// The parts of a task returned by `GET /tasks` needed to reach it, bollard
+/// has no model with its network attachments.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Task {
+    #[serde(rename = "ID")]
+    id: String,
+    slot: Option<u64>,
+    #[serde(default)]
+    status: TaskStatus,
+    #[serde(default)]
+    networks_attachments: Vec<NetworkAttachment>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct TaskStatus {
+    state: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct NetworkAttachment {
+    network: AttachedNetwork,
+    /// Addresses of the task on the network, in CIDR notation.
+    #[serde(default)]
+    addresses: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct AttachedNetwork {
+    #[serde(default)]
+    spec: AttachedNetworkSpec,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct AttachedNetworkSpec {
+    name: String,
+    #[serde(default)]
+    ingress: bool,
+}
+
+impl Task {
+    /// Addresses of the task indexed like container networks, leaving out
+    /// the routing mesh.
+    fn networks(&self) -> HashMap<String, EndpointSettings> {
+        self.networks_attachments
+            .iter()
+            .filter(|attachment| !attachment.network.spec.ingress)
+            .filter_map(|attachment| {
+                let ip = attachment.addresses.first()?.split('/').next()?.to_string();
+                Some((
+                    attachment.network.spec.name.clone(),
+                    EndpointSettings {
+                        ip_address: Some(ip),
+                        ..Default::default()
+                    },
+                ))
+            })
+            .collect()
+    }
+}
+
+/// Percent-encodes a query parameter value.
+fn encode_query(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// Running tasks of the service with ID `service`, in slot order.
+async fn running_tasks(endpoint: &DockerEndpoint, service: &str) -> anyhow::Result<Vec<Task>> {
+    let filters = serde_json::json!({
+        "service": [service],
+        "desired-state": ["running"],
+    });
+    let mut tasks: Vec<Task> = endpoint
+        .get(&format!("/tasks?filters={}", encode_query(&filters.to_string())))
+        .await?;
+    tasks.retain(|task| task.status.state.as_deref() == Some("running"));
+    tasks.sort_by(|a, b| (a.slot, &a.id).cmp(&(b.slot, &b.id)));
+    Ok(tasks)
+}
+
+/// Ports of the service as if they were published by a container, so `host`
+/// backends go through the swarm routing mesh.
+fn published_ports(ports: Vec<EndpointPortConfig>) -> Vec<Port> {
+    ports
+        .into_iter()
+        .filter_map(|port| {
+            Some(Port {
+                ip: None,
+                private_port: u16::try_from(port.target_port?).ok()?,
+                public_port: port.published_port.and_then(|p| u16::try_from(p).ok()),
+                typ: Some(match port.protocol {
+                    Some(EndpointPortConfigProtocolEnum::UDP) => PortTypeEnum::UDP,
+                    Some(EndpointPortConfigProtocolEnum::SCTP) => PortTypeEnum::SCTP,
+                    _ => PortTypeEnum::TCP,
+                }),
+            })
+        })
+        .collect()
+}
+
+/// Lists the swarm services of `endpoint` as containers: one per service with
+/// `vip` backends, or one per task with `tasks` backends. Labels are read from
+/// the service spec.
+pub(super) async fn list_services(
+    endpoint: &DockerEndpoint,
+    options: &DockerOptions,
+    qualify: bool,
+) -> anyhow::Result<Vec<ContainerInfo>> {
+    let services = endpoint
+        .client()?
+        .list_services(None::<ListServicesOptions<String>>)
+        .await?;
+
+    let mut networks = Networks::default();
+    let mut container_info_list = Vec::new();
+
+    for service in services {
+        let spec = service.spec.unwrap_or_default();
+        let labels: HashMap<String, String> = spec
+            .labels
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|(k, _v)| k.starts_with(LABEL_PREFIX))
+            .collect();
+        if labels.is_empty() {
+            continue;
+        }
+
+        let service_name = spec.name.unwrap_or_default();
+        let name = match qualify {
+            true => format!("{}/{}", endpoint.name, service_name),
+            false => service_name.clone(),
+        };
+
+        let swarm_backend = match labels.get(&label_key("swarm.backend")) {
+            Some(mode) => SwarmBackend::from_str(mode, true).unwrap_or_else(|e| {
+                println!("Invalid '{}' label on service '{:?}': {}", label_key("swarm.backend"), name, e);
+                options.swarm_backend
+            }),
+            None => options.swarm_backend,
+        };
+        let requested_network = labels.get(&label_key("network")).or(options.network.as_ref());
+        let backend = labeled_backend(&name, &labels, endpoint.backend.unwrap_or(options.backend));
+        let service_endpoint = service.endpoint.unwrap_or_default();
+        let ports = published_ports(service_endpoint.ports.unwrap_or_default());
+
+        let container = |name: String, ip: String, replica_of: Option<String>| ContainerInfo {
+            name,
+            ip,
+            labels: labels.clone(),
+            ports: ports.clone(),
+            backend,
+            host_address: endpoint.address.clone().or_else(|| options.host_address.clone()),
+            replica_of,
+            label_scope: qualify.then(|| endpoint.name.clone()),
+        };
+
+        match swarm_backend {
+            SwarmBackend::Vip => {
+                // Index the virtual IPs like container networks, leaving out
+                // the routing mesh.
+                let mut vips = HashMap::new();
+                for vip in service_endpoint.virtual_ips.unwrap_or_default() {
+                    let (network_id, addr) = match (vip.network_id, vip.addr) {
+                        (Some(network_id), Some(addr)) => (network_id, addr),
+                        _ => continue,
+                    };
+                    if let Some(network) = networks.get(endpoint, &network_id).await {
+                        if !network.ingress {
+                            let ip = addr.split('/').next().unwrap_or_default().to_string();
+                            vips.insert(
+                                network.name.clone(),
+                                EndpointSettings {
+                                    ip_address: Some(ip),
+                                    ..Default::default()
+                                },
+                            );
+                        }
+                    }
+                }
+                if vips.is_empty() {
+                    println!("WARNING: service '{:?}' has no virtual IP, is it using dnsrr endpoint mode?", name);
+                    continue;
+                }
+
+                let ip = container_ip(&name, &vips, requested_network);
+                container_info_list.push(container(name, ip, None));
+            }
+            SwarmBackend::Tasks => {
+                let tasks = match running_tasks(endpoint, service.id.as_deref().unwrap_or(&service_name)).await {
+                    Ok(tasks) => tasks,
+                    Err(e) => {
+                        println!("WARNING: unable to list the tasks of service '{:?}': {:#}", name, e);
+                        continue;
+                    }
+                };
+                if tasks.is_empty() {
+                    println!("WARNING: service '{:?}' has no running tasks", name);
+                    continue;
+                }
+
+                for task in tasks {
+                    // Global services have no slots, name their tasks by ID.
+                    let task_name = match task.slot {
+                        Some(slot) => format!("{}.{}", name, slot),
+                        None => format!("{}.{}", name, task.id.get(..12).unwrap_or(&task.id)),
+                    };
+                    let networks = task.networks();
+                    if networks.is_empty() {
+                        println!("WARNING: task '{:?}' is not attached to any overlay network", task_name);
+                        continue;
+                    }
+                    let ip = container_ip(&task_name, &networks, requested_network);
+                    container_info_list.push(container(task_name, ip, Some(name.clone())));
+                }
+            }
+        }
+    }
+
+    Ok(container_info_list)
+}
@@ -0,0 +1,156 @@
+use anyhow::Context;
+use async_trait::async_trait;
+use notify::{RecursiveMode, Watcher};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use tokio::sync::mpsc::Receiver;
+use tokio::task;
+
+use crate::provider::Provider;
+use crate::target::traefik::TraefikConfig;
+
+const SUPPORTED_EXTENSIONS: [&str; 4] = ["yaml", "yml", "toml", "json"];
+
+fn is_supported(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| SUPPORTED_EXTENSIONS.contains(&ext))
+        .unwrap_or(false)
+}
+
+/// Serves hand-written Traefik dynamic configuration from YAML, TOML or JSON
+/// files. Each path may be a single file or a directory whose supported files
+/// are all loaded.
+pub struct FileProvider {
+    paths: Vec<PathBuf>,
+    memory: Option<TraefikConfig>,
+    dirty: AtomicBool,
+}
+
+impl FileProvider {
+    pub fn new(paths: Vec<PathBuf>) -> Self {
+        FileProvider {
+            paths,
+            memory: None,
+            dirty: AtomicBool::new(false),
+        }
+    }
+
+    fn config_files(&self) -> anyhow::Result<Vec<PathBuf>> {
+        let mut files = Vec::new();
+
+        for path in &self.paths {
+            if path.is_dir() {
+                let mut entries = std::fs::read_dir(path)
+                    .with_context(|| format!("failed to read directory {}", path.display()))?
+                    .filter_map(|entry| entry.ok().map(|e| e.path()))
+                    .filter(|p| p.is_file() && is_supported(p))
+                    .collect::<Vec<_>>();
+                entries.sort();
+                files.extend(entries);
+            } else {
+                files.push(path.clone());
+            }
+        }
+
+        Ok(files)
+    }
+
+    pub fn read_config_files(&mut self) -> anyhow::Result<TraefikConfig> {
+        if self.dirty.load(std::sync::atomic::Ordering::Relaxed) {
+            self.memory = None;
+        }
+        if let Some(ref memory) = self.memory {
+            return Ok(memory.clone());
+        }
+
+        let mut traefik_config = TraefikConfig::default();
+
+        println!("reading configuration files...");
+        for file in self.config_files()? {
+            let file_config: TraefikConfig = config::Config::builder()
+                .add_source(config::File::from(file.as_path()))
+                .build()
+                .and_then(|c| c.try_deserialize())
+                .with_context(|| format!("failed to load {}", file.display()))?;
+
+            println!(" - Loaded {}", file.display());
+            traefik_config.merge(file_config);
+        }
+
+        self.memory = Some(traefik_config.clone());
+        self.dirty
+            .store(false, std::sync::atomic::Ordering::Relaxed);
+
+        Ok(traefik_config)
+    }
+}
+
+#[async_trait]
+impl Provider for FileProvider {
+    fn name(&self) -> &str {
+        "file"
+    }
+
+    async fn get_config(&mut self) -> anyhow::Result<TraefikConfig> {
+        self.read_config_files()
+    }
+
+    fn mark_dirty(&self) {
+        self.dirty.store(true, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    fn watch_changes(&self) -> Receiver<()> {
+        let (tx, rx) = tokio::sync::mpsc::channel(100);
+
+        let notify_tx = tx.clone();
+        let paths = self.paths.clone();
+        let watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+            match res {
+                Ok(event) => {
+                    let relevant = event
+                        .paths
+                        .iter()
+                        .any(|p| is_supported(p) || paths.contains(p));
+                    if event.kind.is_access() || !relevant {
+                        return;
+                    }
+                    // A full channel already means a refresh is pending.
+                    let _ = notify_tx.try_send(());
+                }
+                Err(e) => {
+                    eprintln!("Error watching configuration files: {:?}", e);
+                }
+            }
+        });
+
+        let mut watcher = match watcher {
+            Ok(watcher) => watcher,
+            Err(e) => {
+                eprintln!("Unable to watch configuration files: {:?}", e);
+                return rx;
+            }
+        };
+
+        for path in &self.paths {
+            // Watch the parent of single files, editors usually replace files
+            // instead of writing to them in place.
+            let target = if path.is_dir() {
+                path.as_path()
+            } else {
+                path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."))
+            };
+            if let Err(e) = watcher.watch(target, RecursiveMode::NonRecursive) {
+                eprintln!("Unable to watch {}: {:?}", target.display(), e);
+            }
+        }
+
+        // Keep the watcher alive for as long as someone listens for changes.
+        task::spawn(async move {
+            tx.closed().await;
+            drop(watcher);
+        });
+
+        rx
+    }
+}
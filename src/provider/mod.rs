@@ -1 +1,39 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use tokio::sync::mpsc::Receiver;
+use tokio::sync::Mutex;
+
+use crate::target::traefik::TraefikConfig;
+
 pub mod docker;
+pub mod file;
+
+/// A source of endpoints that can be exposed through Traefik.
+///
+/// Every registered provider contributes its own `TraefikConfig`, which the web
+/// layer merges into the single configuration served to Traefik.
+#[async_trait]
+pub trait Provider: Send {
+    /// Human readable name, used in logs.
+    fn name(&self) -> &str;
+
+    /// Discovers the endpoints currently known to this provider.
+    async fn get_config(&mut self) -> anyhow::Result<TraefikConfig>;
+
+    /// Why the last configuration returned by `get_config` may be out of
+    /// date, e.g. when part of it comes from a source that is unreachable.
+    fn stale_reason(&self) -> Option<String> {
+        None
+    }
+
+    /// Invalidates any cached configuration so the next `get_config` call
+    /// rebuilds it from scratch.
+    fn mark_dirty(&self);
+
+    /// Returns a channel that yields a message every time the provider detects
+    /// a change that should trigger a refresh.
+    fn watch_changes(&self) -> Receiver<()>;
+}
+
+pub type SharedProvider = Arc<Mutex<dyn Provider>>;
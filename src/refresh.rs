@@ -0,0 +1,169 @@
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use sha2::{Digest, Sha256};
+use tokio::sync::{watch, Notify};
+use tokio::time::{timeout, timeout_at, Instant};
+
+use crate::provider::SharedProvider;
+use crate::target::traefik::TraefikConfig;
+
+/// Waits before retrying a failed build, unless a change comes first.
+const RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// A burst of changes delays a rebuild by at most this many debounce periods.
+const MAX_DEBOUNCES: u32 = 10;
+
+/// A configuration built from every provider, along with its JSON form and a
+/// hash of it, computed once instead of for every request.
+#[derive(Debug)]
+pub struct ConfigSnapshot {
+    pub config: TraefikConfig,
+    pub json: String,
+    /// Quoted hex SHA-256 of `json`, used as the `ETag` of the configuration.
+    pub etag: String,
+}
+
+impl ConfigSnapshot {
+    pub fn new(config: TraefikConfig) -> anyhow::Result<Self> {
+        let json = serde_json::to_string(&config)?;
+        let etag = format!("\"{:x}\"", Sha256::digest(json.as_bytes()));
+        Ok(ConfigSnapshot { config, json, etag })
+    }
+}
+
+/// Why the published configuration may be out of date.
+#[derive(Debug, Clone)]
+pub struct Stale {
+    pub reason: String,
+    /// When the configuration stopped being refreshed.
+    pub since: SystemTime,
+}
+
+/// What is published to readers of the configuration.
+#[derive(Debug, Clone, Default)]
+pub struct Snapshot {
+    /// The last configuration built, `None` until a build succeeds.
+    pub current: Option<Arc<ConfigSnapshot>>,
+    /// Set while `current` can't be refreshed, e.g. because Docker is
+    /// unreachable. It is still served, as the last known good configuration.
+    pub stale: Option<Stale>,
+    /// Why there is no configuration yet, when the first builds failed.
+    pub error: Option<String>,
+}
+
+/// Merges the configuration of every provider. Providers that fail contribute
+/// their last good configuration and are reported in the returned failures.
+/// Building fails when a provider never succeeded, as publishing a
+/// configuration without it would tear down its routes.
+async fn build(
+    providers: &[SharedProvider],
+    last_good: &mut [Option<TraefikConfig>],
+) -> anyhow::Result<(TraefikConfig, Vec<String>)> {
+    let mut traefik_cfg = TraefikConfig::default();
+    let mut failures = Vec::new();
+    for (provider, last_good) in providers.iter().zip(last_good.iter_mut()) {
+        let mut provider = provider.lock().await;
+        match provider.get_config().await {
+            Ok(config) => {
+                if let Some(reason) = provider.stale_reason() {
+                    failures.push(format!("provider '{}': {}", provider.name(), reason));
+                }
+                *last_good = Some(config.clone());
+                traefik_cfg.merge(config);
+            }
+            Err(e) => {
+                let e = e.context(format!("provider '{}'", provider.name()));
+                match last_good {
+                    Some(config) => {
+                        eprintln!("Using the last known good configuration: {:?}", e);
+                        failures.push(format!("{:#}", e));
+                        traefik_cfg.merge(config.clone());
+                    }
+                    None => return Err(e),
+                }
+            }
+        }
+    }
+    Ok((traefik_cfg, failures))
+}
+
+/// Publishes a built configuration, stale when some providers failed.
+fn publish(snapshot: &watch::Sender<Snapshot>, built: ConfigSnapshot, failures: Vec<String>) {
+    snapshot.send_if_modified(|published| {
+        let stale = match failures.is_empty() {
+            true => None,
+            false => Some(Stale {
+                reason: failures.join("; "),
+                since: published.stale.as_ref().map(|s| s.since).unwrap_or_else(SystemTime::now),
+            }),
+        };
+        // Only wake up readers when the configuration or its staleness changed.
+        let changed = published.current.as_ref().map(|c| &c.etag) != Some(&built.etag)
+            || published.stale.is_some() != stale.is_some();
+        *published = Snapshot {
+            current: Some(Arc::new(built)),
+            stale,
+            error: None,
+        };
+        changed
+    });
+}
+
+/// Marks the published configuration as stale after a failed build.
+fn publish_failure(snapshot: &watch::Sender<Snapshot>, error: &anyhow::Error) {
+    snapshot.send_if_modified(|published| {
+        if published.current.is_none() {
+            published.error = Some(format!("{:#}", error));
+            return false;
+        }
+        let changed = published.stale.is_none();
+        published.stale = Some(Stale {
+            reason: format!("{:#}", error),
+            since: published.stale.as_ref().map(|s| s.since).unwrap_or_else(SystemTime::now),
+        });
+        changed
+    });
+}
+
+/// Rebuilds the configuration every time `changes` is notified, once no other
+/// change arrived for `debounce`, and publishes it to `snapshot`. Failed builds
+/// keep the previous configuration, marked as stale, and are retried.
+pub async fn run(
+    providers: Vec<SharedProvider>,
+    debounce: Duration,
+    changes: Arc<Notify>,
+    snapshot: watch::Sender<Snapshot>,
+) {
+    let mut last_good = vec![None; providers.len()];
+
+    loop {
+        let built = build(&providers, &mut last_good)
+            .await
+            .and_then(|(config, failures)| Ok((ConfigSnapshot::new(config)?, failures)));
+        let failed = match built {
+            Ok((built, failures)) => {
+                let failed = !failures.is_empty();
+                publish(&snapshot, built, failures);
+                failed
+            }
+            Err(e) => {
+                eprintln!("Unable to build the configuration: {:?}", e);
+                publish_failure(&snapshot, &e);
+                true
+            }
+        };
+
+        if failed {
+            let _ = timeout(RETRY_INTERVAL, changes.notified()).await;
+        } else {
+            changes.notified().await;
+        }
+
+        let deadline = Instant::now() + debounce * MAX_DEBOUNCES;
+        while timeout_at(deadline.min(Instant::now() + debounce), changes.notified())
+            .await
+            .is_ok()
+        {}
+    }
+}
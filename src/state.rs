@@ -0,0 +1,52 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use anyhow::Context;
+use tokio::sync::watch;
+
+use crate::output::write_atomically;
+use crate::refresh::{ConfigSnapshot, Snapshot, Stale};
+use crate::target::traefik::TraefikConfig;
+
+/// Loads the configuration persisted by a previous run, to be served as stale
+/// until the providers are synced. A missing state file is not an error.
+pub fn load(path: &Path) -> anyhow::Result<Option<Snapshot>> {
+    let json = match std::fs::read_to_string(path) {
+        Ok(json) => json,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e).with_context(|| format!("failed to read {}", path.display())),
+    };
+    let config: TraefikConfig =
+        serde_json::from_str(&json).with_context(|| format!("failed to parse {}", path.display()))?;
+    let since = std::fs::metadata(path)
+        .and_then(|m| m.modified())
+        .unwrap_or_else(|_| SystemTime::now());
+
+    Ok(Some(Snapshot {
+        current: Some(Arc::new(ConfigSnapshot::new(config)?)),
+        stale: Some(Stale {
+            reason: format!("loaded from {}, waiting for the first sync", path.display()),
+            since,
+        }),
+        error: None,
+    }))
+}
+
+/// Persists every new configuration to `path`.
+pub async fn run(path: PathBuf, mut snapshot: watch::Receiver<Snapshot>) {
+    let mut written = snapshot.borrow_and_update().current.as_ref().map(|c| c.etag.clone());
+
+    while snapshot.changed().await.is_ok() {
+        let current = snapshot.borrow_and_update().current.clone();
+        if let Some(current) = current {
+            if written.as_ref() == Some(&current.etag) {
+                continue;
+            }
+            match write_atomically(&path, &current.json).await {
+                Ok(()) => written = Some(current.etag.clone()),
+                Err(e) => eprintln!("Unable to persist the configuration: {:?}", e),
+            }
+        }
+    }
+}
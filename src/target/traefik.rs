@@ -0,0 +1,854 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RouterConfig {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub entry_points: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub middlewares: Vec<String>,
+
+    #[serde(default)]
+    pub service: String,
+
+    pub rule: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rule_syntax: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub priority: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tls: Option<TlsConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub observability: Option<ObservabilityConfig>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TlsConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub options: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cert_resolver: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub domains: Option<Vec<DomainConfig>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DomainConfig {
+    pub main: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sans: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ObservabilityConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub access_logs: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tracing: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metrics: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ServiceConfig {
+    Failover(FailoverConfig),
+    LoadBalancer(LoadBalancerConfig),
+    Mirroring(MirroringConfig),
+    Weighted(WeightedConfig),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FailoverConfig {
+    pub service: String,
+    pub fallback: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub health_check: Option<HealthCheckConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LoadBalancerConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sticky: Option<StickyConfig>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub servers: Vec<ServerConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub health_check: Option<HealthCheckConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pass_host_header: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_forwarding: Option<ResponseForwardingConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub servers_transport: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StickyConfig {
+    pub cookie: CookieConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CookieConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub secure: bool,
+    #[serde(default)]
+    pub http_only: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub same_site: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_age: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerConfig {
+    pub url: String,
+    #[serde(default = "default_weight")]
+    pub weight: u32,
+    #[serde(default)]
+    pub preserve_path: bool,
+}
+
+fn default_weight() -> u32 {
+    1
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HealthCheckConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scheme: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mode: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub method: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub port: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interval: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub follow_redirects: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub headers: Option<std::collections::BTreeMap<String, String>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResponseForwardingConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub flush_interval: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MirroringConfig {
+    pub service: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mirror_body: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_body_size: Option<u32>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mirrors: Vec<MirrorConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub health_check: Option<HealthCheckConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MirrorConfig {
+    pub name: String,
+    pub percent: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WeightedConfig {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub services: Vec<WeightedServiceConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sticky: Option<StickyConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub health_check: Option<HealthCheckConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WeightedServiceConfig {
+    pub name: String,
+    pub weight: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[allow(clippy::large_enum_variant)]
+pub enum MiddlewareConfig {
+    AddPrefix(AddPrefixConfig),
+    BasicAuth(BasicAuthConfig),
+    Buffering(BufferingConfig),
+    Chain(ChainConfig),
+    CircuitBreaker(CircuitBreakerConfig),
+    Compress(CompressConfig),
+    ContentType(ContentTypeConfig),
+    DigestAuth(DigestAuthConfig),
+    Errors(ErrorsConfig),
+    ForwardAuth(ForwardAuthConfig),
+    GrpcWeb(GrpcWebConfig),
+    Headers(HeadersConfig),
+    IpAllowList(IpAllowListConfig),
+    IpWhiteList(IpWhiteListConfig),
+    InFlightReq(InFlightReqConfig),
+    PassTlsClientCert(PassTlsClientCertConfig),
+    Plugin(PluginConfig),
+    RateLimit(RateLimitConfig),
+    RedirectRegex(RedirectRegexConfig),
+    RedirectScheme(RedirectSchemeConfig),
+    ReplacePath(ReplacePathConfig),
+    ReplacePathRegex(ReplacePathRegexConfig),
+    Retry(RetryConfig),
+    StripPrefix(StripPrefixConfig),
+    StripPrefixRegex(StripPrefixRegexConfig),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AddPrefixConfig {
+    pub prefix: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BasicAuthConfig {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub users: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub users_file: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub realm: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remove_header: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub header_field: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BufferingConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_request_body_bytes: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mem_request_body_bytes: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_response_body_bytes: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mem_response_body_bytes: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_expression: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChainConfig {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub middlewares: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CircuitBreakerConfig {
+    pub expression: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub check_period: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fallback_duration: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recovery_duration: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_code: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompressConfig {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub excluded_content_types: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub included_content_types: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_response_body_bytes: Option<u32>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub encodings: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_encoding: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContentTypeConfig {
+    #[serde(default)]
+    pub auto_detect: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DigestAuthConfig {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub users: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub users_file: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remove_header: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub realm: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub header_field: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ErrorsConfig {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub status: Vec<String>,
+    pub service: String,
+    pub query: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ForwardAuthConfig {
+    pub address: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tls: Option<TlsAuthConfig>,
+    #[serde(default)]
+    pub trust_forward_header: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub auth_response_headers: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auth_response_headers_regex: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub auth_request_headers: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub add_auth_cookies_to_response: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub header_field: Option<String>,
+    #[serde(default)]
+    pub forward_body: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_body_size: Option<u32>,
+    #[serde(default)]
+    pub preserve_location_header: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TlsAuthConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ca: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cert: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+    #[serde(default)]
+    pub insecure_skip_verify: bool,
+    #[serde(default)]
+    pub ca_optional: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GrpcWebConfig {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allow_origins: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HeadersConfig {
+    #[serde(default, skip_serializing_if = "std::collections::BTreeMap::is_empty")]
+    pub custom_request_headers: std::collections::BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "std::collections::BTreeMap::is_empty")]
+    pub custom_response_headers: std::collections::BTreeMap<String, String>,
+    #[serde(default)]
+    pub access_control_allow_credentials: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub access_control_allow_headers: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub access_control_allow_methods: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub access_control_allow_origin_list: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub access_control_allow_origin_list_regex: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub access_control_expose_headers: Vec<String>,
+    #[serde(default)]
+    pub access_control_max_age: u32,
+    #[serde(default)]
+    pub add_vary_header: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allowed_hosts: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hosts_proxy_headers: Vec<String>,
+    #[serde(default, skip_serializing_if = "std::collections::BTreeMap::is_empty")]
+    pub ssl_proxy_headers: std::collections::BTreeMap<String, String>,
+    #[serde(default)]
+    pub sts_seconds: u32,
+    #[serde(default)]
+    pub sts_include_subdomains: bool,
+    #[serde(default)]
+    pub sts_preload: bool,
+    #[serde(default)]
+    pub force_sts_header: bool,
+    #[serde(default)]
+    pub frame_deny: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub custom_frame_options_value: Option<String>,
+    #[serde(default)]
+    pub content_type_nosniff: bool,
+    #[serde(default)]
+    pub browser_xss_filter: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub custom_browser_xss_value: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_security_policy: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_security_policy_report_only: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub public_key: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub referrer_policy: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub permissions_policy: Option<String>,
+    #[serde(default)]
+    pub is_development: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub feature_policy: Option<String>,
+    #[serde(default)]
+    pub ssl_redirect: bool,
+    #[serde(default)]
+    pub ssl_temporary_redirect: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ssl_host: Option<String>,
+    #[serde(default)]
+    pub ssl_force_host: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IpAllowListConfig {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub source_range: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ip_strategy: Option<IpStrategyConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reject_status_code: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IpStrategyConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub depth: Option<u32>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub excluded_ips: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ipv6_subnet: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IpWhiteListConfig {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub source_range: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ip_strategy: Option<IpStrategyConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InFlightReqConfig {
+    pub amount: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_criterion: Option<SourceCriterionConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SourceCriterionConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ip_strategy: Option<IpStrategyConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_header_name: Option<String>,
+    #[serde(default)]
+    pub request_host: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PassTlsClientCertConfig {
+    #[serde(default)]
+    pub pem: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub info: Option<CertInfoConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default, rename_all = "camelCase")]
+pub struct CertInfoConfig {
+    pub not_after: bool,
+    pub not_before: bool,
+    pub sans: bool,
+    pub serial_number: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subject: Option<SubjectConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub issuer: Option<IssuerConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default, rename_all = "camelCase")]
+pub struct SubjectConfig {
+    pub country: bool,
+    pub province: bool,
+    pub locality: bool,
+    pub organization: bool,
+    pub organizational_unit: bool,
+    pub common_name: bool,
+    pub serial_number: bool,
+    pub domain_component: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default, rename_all = "camelCase")]
+pub struct IssuerConfig {
+    pub country: bool,
+    pub province: bool,
+    pub locality: bool,
+    pub organization: bool,
+    pub common_name: bool,
+    pub serial_number: bool,
+    pub domain_component: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PluginConfig {
+    #[serde(default, skip_serializing_if = "std::collections::BTreeMap::is_empty")]
+    pub plugin_conf: std::collections::BTreeMap<String, std::collections::BTreeMap<String, String>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RateLimitConfig {
+    pub average: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub period: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub burst: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_criterion: Option<SourceCriterionConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RedirectRegexConfig {
+    pub regex: String,
+    pub replacement: String,
+    #[serde(default)]
+    pub permanent: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RedirectSchemeConfig {
+    pub scheme: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub port: Option<String>,
+    #[serde(default)]
+    pub permanent: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplacePathConfig {
+    pub path: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplacePathRegexConfig {
+    pub regex: String,
+    pub replacement: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RetryConfig {
+    pub attempts: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub initial_interval: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StripPrefixConfig {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub prefixes: Vec<String>,
+    #[serde(default)]
+    pub force_slash: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StripPrefixRegexConfig {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub regex: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct HttpConfig {
+    #[serde(default, skip_serializing_if = "std::collections::BTreeMap::is_empty")]
+    pub routers: std::collections::BTreeMap<String, RouterConfig>,
+    #[serde(default, skip_serializing_if = "std::collections::BTreeMap::is_empty")]
+    pub services: std::collections::BTreeMap<String, ServiceConfig>,
+    #[serde(default, skip_serializing_if = "std::collections::BTreeMap::is_empty")]
+    pub middlewares: std::collections::BTreeMap<String, MiddlewareConfig>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TcpRouterConfig {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub entry_points: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub middlewares: Vec<String>,
+
+    #[serde(default)]
+    pub service: String,
+
+    pub rule: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rule_syntax: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub priority: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tls: Option<TcpTlsConfig>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TcpTlsConfig {
+    #[serde(default)]
+    pub passthrough: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub options: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cert_resolver: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub domains: Option<Vec<DomainConfig>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TcpServiceConfig {
+    LoadBalancer(TcpLoadBalancerConfig),
+    Weighted(TcpWeightedConfig),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TcpLoadBalancerConfig {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub servers: Vec<TcpServerConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proxy_protocol: Option<ProxyProtocolConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub servers_transport: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TcpServerConfig {
+    pub address: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tls: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProxyProtocolConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TcpWeightedConfig {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub services: Vec<WeightedServiceConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TcpMiddlewareConfig {
+    InFlightConn(InFlightConnConfig),
+    IpAllowList(TcpIpAllowListConfig),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InFlightConnConfig {
+    pub amount: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TcpIpAllowListConfig {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub source_range: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct TcpConfig {
+    #[serde(default, skip_serializing_if = "std::collections::BTreeMap::is_empty")]
+    pub routers: std::collections::BTreeMap<String, TcpRouterConfig>,
+    #[serde(default, skip_serializing_if = "std::collections::BTreeMap::is_empty")]
+    pub services: std::collections::BTreeMap<String, TcpServiceConfig>,
+    #[serde(default, skip_serializing_if = "std::collections::BTreeMap::is_empty")]
+    pub middlewares: std::collections::BTreeMap<String, TcpMiddlewareConfig>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UdpRouterConfig {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub entry_points: Vec<String>,
+
+    #[serde(default)]
+    pub service: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum UdpServiceConfig {
+    LoadBalancer(UdpLoadBalancerConfig),
+    Weighted(UdpWeightedConfig),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UdpLoadBalancerConfig {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub servers: Vec<UdpServerConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UdpServerConfig {
+    pub address: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UdpWeightedConfig {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub services: Vec<WeightedServiceConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct UdpConfig {
+    #[serde(default, skip_serializing_if = "std::collections::BTreeMap::is_empty")]
+    pub routers: std::collections::BTreeMap<String, UdpRouterConfig>,
+    #[serde(default, skip_serializing_if = "std::collections::BTreeMap::is_empty")]
+    pub services: std::collections::BTreeMap<String, UdpServiceConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct TraefikConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub http: Option<HttpConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tcp: Option<TcpConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub udp: Option<UdpConfig>,
+}
+
+fn merge_section<T>(
+    kind: &str,
+    into: &mut std::collections::BTreeMap<String, T>,
+    from: std::collections::BTreeMap<String, T>,
+) {
+    for (name, value) in from {
+        if into.insert(name.clone(), value).is_some() {
+            eprintln!("{} '{}' is defined more than once, keeping the last definition", kind, name);
+        }
+    }
+}
+
+impl HttpConfig {
+    pub fn merge(&mut self, other: HttpConfig) {
+        merge_section("Router", &mut self.routers, other.routers);
+        merge_section("Service", &mut self.services, other.services);
+        merge_section("Middleware", &mut self.middlewares, other.middlewares);
+    }
+}
+
+impl TcpConfig {
+    pub fn is_empty(&self) -> bool {
+        self.routers.is_empty() && self.services.is_empty() && self.middlewares.is_empty()
+    }
+
+    pub fn merge(&mut self, other: TcpConfig) {
+        merge_section("TCP router", &mut self.routers, other.routers);
+        merge_section("TCP service", &mut self.services, other.services);
+        merge_section("TCP middleware", &mut self.middlewares, other.middlewares);
+    }
+}
+
+impl UdpConfig {
+    pub fn is_empty(&self) -> bool {
+        self.routers.is_empty() && self.services.is_empty()
+    }
+
+    pub fn merge(&mut self, other: UdpConfig) {
+        merge_section("UDP router", &mut self.routers, other.routers);
+        merge_section("UDP service", &mut self.services, other.services);
+    }
+}
+
+impl TraefikConfig {
+    /// Merges `other` into this configuration. On name collisions the entry from
+    /// `other` wins.
+    pub fn merge(&mut self, other: TraefikConfig) {
+        if let Some(http) = other.http {
+            self.http.get_or_insert_with(HttpConfig::default).merge(http);
+        }
+        if let Some(tcp) = other.tcp {
+            self.tcp.get_or_insert_with(TcpConfig::default).merge(tcp);
+        }
+        if let Some(udp) = other.udp {
+            self.udp.get_or_insert_with(UdpConfig::default).merge(udp);
+        }
+    }
+}
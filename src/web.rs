@@ -1,39 +1,226 @@
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::net::ToSocketAddrs;
+use tokio::sync::watch;
+use tokio::time::{timeout_at, Instant};
 
-use axum::extract::State;
-use axum::response::IntoResponse;
+use chrono::{DateTime, Utc};
+
+use futures::stream::{self, Stream};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use axum::extract::{Query, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{AppendHeaders, IntoResponse, Response};
 use axum::routing::get;
-use axum::Router;
-use tokio::sync::Mutex;
+use axum::{Json, Router};
 
-use crate::provider::docker::DockerProvider;
-use crate::target::traefik::TraefikConfig;
+use crate::refresh::{Snapshot, Stale};
 
 pub struct App {
     shutdown_signal: tokio::sync::oneshot::Sender<()>,
 }
 
 struct AppState {
-    docker_client: Arc<Mutex<DockerProvider>>,
+    config: watch::Receiver<Snapshot>,
 }
 
-async fn get_traefik_config(State(state): State<Arc<AppState>>) -> impl IntoResponse {
-    let traefik_cfg = state.docker_client.lock().await.get_exposable_containers_info().await.unwrap();
-    let cfg = serde_json::to_string::<TraefikConfig>(&traefik_cfg.into()).unwrap();
+/// Longest a `?wait=` request is held for.
+const MAX_WAIT: Duration = Duration::from_secs(300);
+
+#[derive(Debug, Deserialize)]
+struct ConfigParams {
+    /// Long polling: when the client already has the current configuration
+    /// (per `If-None-Match`), wait up to this long for it to change, e.g. `30s`.
+    wait: Option<String>,
+}
+
+/// Parses durations such as `30s`, `500ms`, `2m` or `30` (seconds).
+fn parse_wait(wait: &str) -> Option<Duration> {
+    let wait = wait.trim();
+    let (amount, unit) = wait.split_at(wait.find(|c: char| !c.is_ascii_digit()).unwrap_or(wait.len()));
+    let amount = amount.parse::<u64>().ok()?;
+    match unit {
+        "ms" => Some(Duration::from_millis(amount)),
+        "" | "s" => Some(Duration::from_secs(amount)),
+        // Saturates, the caller caps waits to `MAX_WAIT` anyway.
+        "m" => Some(Duration::from_secs(amount.saturating_mul(60))),
+        _ => None,
+    }
+}
 
-    ([("Content-Type", "application/json")], cfg)
+/// Whether an `If-None-Match` header matches `etag`.
+fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    if_none_match
+        .split(',')
+        .map(str::trim)
+        .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+}
+
+/// Errors answered by the web API.
+#[derive(Debug)]
+enum ApiError {
+    /// No configuration was built yet, with the reason of the last failure.
+    NotReady(Option<String>),
+    InvalidWait(String),
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let (status, message) = match self {
+            ApiError::NotReady(Some(reason)) => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                format!("configuration not built yet: {}", reason),
+            ),
+            ApiError::NotReady(None) => (StatusCode::SERVICE_UNAVAILABLE, "configuration not built yet".to_string()),
+            ApiError::InvalidWait(wait) => (StatusCode::BAD_REQUEST, format!("invalid wait duration '{}'", wait)),
+        };
+        (status, Json(json!({ "error": message }))).into_response()
+    }
+}
+
+/// Headers flagging the last known good configuration being served while it
+/// can't be refreshed.
+fn stale_headers(stale: Option<&Stale>) -> Vec<(&'static str, String)> {
+    match stale {
+        Some(stale) => vec![
+            ("X-Config-Stale", "true".to_string()),
+            (
+                "X-Config-Stale-Since",
+                DateTime::<Utc>::from(stale.since)
+                    .format("%a, %d %b %Y %H:%M:%S GMT")
+                    .to_string(),
+            ),
+            (
+                "X-Config-Stale-Reason",
+                stale
+                    .reason
+                    .chars()
+                    .map(|c| if c.is_ascii_graphic() { c } else { ' ' })
+                    .collect(),
+            ),
+        ],
+        None => Vec::new(),
+    }
+}
+
+async fn get_traefik_config(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<ConfigParams>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let wait = match params.wait {
+        Some(wait) => Some(parse_wait(&wait).ok_or(ApiError::InvalidWait(wait))?.min(MAX_WAIT)),
+        None => None,
+    };
+    let if_none_match = headers.get("If-None-Match").and_then(|v| v.to_str().ok());
+    let not_modified = |snapshot: &Snapshot| match (&snapshot.current, if_none_match) {
+        (Some(current), Some(if_none_match)) => etag_matches(if_none_match, &current.etag),
+        _ => false,
+    };
+
+    let mut config = state.config.clone();
+    let mut snapshot = config.borrow_and_update().clone();
+    if let Some(wait) = wait {
+        let deadline = Instant::now() + wait;
+        // Staleness changes are published too, keep waiting through them.
+        while not_modified(&snapshot) && matches!(timeout_at(deadline, config.changed()).await, Ok(Ok(()))) {
+            snapshot = config.borrow_and_update().clone();
+        }
+    }
+
+    let stale = AppendHeaders(stale_headers(snapshot.stale.as_ref()));
+    let current = match snapshot.current {
+        Some(ref current) => current,
+        None => return Err(ApiError::NotReady(snapshot.error)),
+    };
+    if not_modified(&snapshot) {
+        return Ok((StatusCode::NOT_MODIFIED, [("ETag", current.etag.clone())], stale).into_response());
+    }
+    Ok((
+        [("Content-Type", "application/json"), ("ETag", current.etag.as_str())],
+        stale,
+        current.json.clone(),
+    )
+        .into_response())
+}
+
+#[derive(Debug, Deserialize)]
+struct StreamParams {
+    /// Send JSON merge patches (RFC 7396) after the first configuration,
+    /// instead of the full configuration on every change.
+    #[serde(default)]
+    diff: bool,
+}
+
+/// JSON merge patch (RFC 7396) that turns `old` into `new`.
+fn merge_patch(old: &Value, new: &Value) -> Value {
+    match (old, new) {
+        (Value::Object(old), Value::Object(new)) => {
+            let mut patch = serde_json::Map::new();
+            for (key, old_value) in old {
+                match new.get(key) {
+                    None => {
+                        patch.insert(key.clone(), Value::Null);
+                    }
+                    Some(new_value) if new_value != old_value => {
+                        patch.insert(key.clone(), merge_patch(old_value, new_value));
+                    }
+                    Some(_) => {}
+                }
+            }
+            for (key, new_value) in new {
+                if !old.contains_key(key) {
+                    patch.insert(key.clone(), new_value.clone());
+                }
+            }
+            Value::Object(patch)
+        }
+        _ => new.clone(),
+    }
+}
+
+/// Streams the configuration as server-sent events: a `config` event with the
+/// current configuration, then one `config` (or `diff`) event per change.
+async fn stream_traefik_config(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<StreamParams>,
+) -> Sse<impl Stream<Item = Result<Event, axum::Error>>> {
+    let events = stream::unfold((state.config.clone(), None), move |(mut config, sent): (_, Option<Value>)| async move {
+        loop {
+            let snapshot = config.borrow_and_update().current.clone();
+            if let Some(snapshot) = snapshot {
+                let current = serde_json::to_value(&snapshot.config).unwrap_or_default();
+                // Rebuilds often produce the same configuration.
+                if sent.as_ref() != Some(&current) {
+                    let event = match sent {
+                        Some(ref previous) if params.diff => {
+                            Event::default().event("diff").json_data(merge_patch(previous, &current))
+                        }
+                        _ => Event::default().event("config").json_data(&current),
+                    };
+                    return Some((event, (config, Some(current))));
+                }
+            }
+            config.changed().await.ok()?;
+        }
+    });
+
+    Sse::new(events).keep_alive(KeepAlive::default())
 }
 
 impl App {
-    pub fn new<A: ToSocketAddrs + 'static + Send>(addr: A, docker_client: Arc<Mutex<DockerProvider>>) -> anyhow::Result<Self> {
+    pub fn new<A: ToSocketAddrs + 'static + Send>(addr: A, config: watch::Receiver<Snapshot>) -> anyhow::Result<Self> {
         let state = Arc::new(AppState {
-            docker_client,
+            config,
         });
 
         let router = Router::new()
             .route("/", get(|| async { "Hello, World!" }))
             .route("/traefik", get(get_traefik_config))
+            .route("/traefik/events", get(stream_traefik_config))
             .with_state(state.clone());
 
         let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel::<()>();
@@ -56,3 +243,85 @@ impl App {
         let _ = self.shutdown_signal.send(());
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_wait_units() {
+        assert_eq!(parse_wait("30"), Some(Duration::from_secs(30)));
+        assert_eq!(parse_wait("30s"), Some(Duration::from_secs(30)));
+        assert_eq!(parse_wait(" 500ms "), Some(Duration::from_millis(500)));
+        assert_eq!(parse_wait("2m"), Some(Duration::from_secs(120)));
+        assert_eq!(parse_wait("0"), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn parse_wait_invalid() {
+        assert_eq!(parse_wait(""), None);
+        assert_eq!(parse_wait("s"), None);
+        assert_eq!(parse_wait("-1s"), None);
+        assert_eq!(parse_wait("1.5s"), None);
+        assert_eq!(parse_wait("1h"), None);
+        assert_eq!(parse_wait("99999999999999999999s"), None);
+    }
+
+    #[test]
+    fn parse_wait_overflow() {
+        assert_eq!(parse_wait("307445734561825861m"), Some(Duration::from_secs(u64::MAX)));
+        assert_eq!(parse_wait(&format!("{}ms", u64::MAX)), Some(Duration::from_millis(u64::MAX)));
+    }
+
+    #[test]
+    fn etag_matching() {
+        let etag = "\"abc\"";
+        assert!(etag_matches("\"abc\"", etag));
+        assert!(etag_matches("W/\"abc\"", etag));
+        assert!(etag_matches("\"xyz\", \"abc\"", etag));
+        assert!(etag_matches("*", etag));
+        assert!(!etag_matches("\"xyz\"", etag));
+        assert!(!etag_matches("abc", etag));
+        assert!(!etag_matches("", etag));
+    }
+
+    #[test]
+    fn merge_patch_removed_keys() {
+        let old = json!({ "routers": { "a": { "rule": "Host(`a`)" }, "b": { "rule": "Host(`b`)" } } });
+        let new = json!({ "routers": { "a": { "rule": "Host(`a`)" } } });
+        assert_eq!(merge_patch(&old, &new), json!({ "routers": { "b": null } }));
+
+        let old = json!({ "http": {}, "tcp": {} });
+        let new = json!({ "http": {} });
+        assert_eq!(merge_patch(&old, &new), json!({ "tcp": null }));
+    }
+
+    #[test]
+    fn merge_patch_changed_nested_objects() {
+        let old = json!({ "routers": { "a": { "rule": "Host(`a`)", "service": "a", "priority": 1 } } });
+        let new = json!({ "routers": { "a": { "rule": "Host(`b`)", "service": "a", "tls": {} } } });
+        assert_eq!(
+            merge_patch(&old, &new),
+            json!({ "routers": { "a": { "rule": "Host(`b`)", "priority": null, "tls": {} } } })
+        );
+
+        // An object replacing a scalar, and the other way around.
+        let old = json!({ "a": 1, "b": { "c": 2 } });
+        let new = json!({ "a": { "c": 2 }, "b": 1 });
+        assert_eq!(merge_patch(&old, &new), json!({ "a": { "c": 2 }, "b": 1 }));
+    }
+
+    #[test]
+    fn merge_patch_replaced_arrays() {
+        let old = json!({ "servers": [{ "url": "http://a" }, { "url": "http://b" }], "entryPoints": ["http"] });
+        let new = json!({ "servers": [{ "url": "http://a" }], "entryPoints": ["http"] });
+        // Arrays are replaced as a whole, there is no way to patch elements.
+        assert_eq!(merge_patch(&old, &new), json!({ "servers": [{ "url": "http://a" }] }));
+    }
+
+    #[test]
+    fn merge_patch_unchanged() {
+        let config = json!({ "http": { "routers": { "a": { "rule": "Host(`a`)" } } } });
+        assert_eq!(merge_patch(&config, &config), json!({}));
+    }
+}